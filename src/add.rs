@@ -1,11 +1,15 @@
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
 
 use anyhow::{anyhow, bail, Result};
 use itertools::Itertools;
 use serde_json::Value;
+use url::Url;
+
+use crate::platform::Platform;
+use crate::retry::Attempt;
+use crate::RunContext;
 
 /// User-facing options for `lifter add github`.
 ///
@@ -14,6 +18,9 @@ use serde_json::Value;
 /// download or inspect the archive while creating the config entry.
 pub struct AddGithubOptions {
     pub repo: String,
+    /// GitHub Enterprise Server host (or its API URL). Defaults to
+    /// github.com.
+    pub host: Option<String>,
     pub section_name: Option<String>,
     pub asset_filter: Option<String>,
     pub extract: Option<String>,
@@ -27,6 +34,7 @@ pub struct AddedGithubDefinition {
     pub asset_name: String,
     pub version: String,
     pub entry: String,
+    pub template_name: String,
     pub wrote_template: bool,
     pub wrote_file: bool,
 }
@@ -36,11 +44,62 @@ struct GithubRelease {
     assets: Vec<String>,
 }
 
-const GITHUB_API_TEMPLATE: &str = "[template:github_api_latest]\nmethod = api_json\npage_url = https://api.github.com/repos/{project}/releases/latest\nversion_tag = $.tag_name\nanchor_tag = $.assets.*.browser_download_url\n";
+const GITHUB_API: &str = "https://api.github.com";
+
+/// Where a GitHub host's REST API lives, and the name of the template for
+/// its releases.
+struct GithubApi {
+    base: String,
+    template_name: String,
+}
+
+impl GithubApi {
+    /// github.com when `host` is `None`. Otherwise a GitHub Enterprise
+    /// Server, whose API is under `/api/v3` on the host itself unless
+    /// `host` is a URL with a path.
+    fn for_host(host: Option<&str>) -> Result<Self> {
+        let host = host.map(str::trim).unwrap_or("github.com");
+        if ["github.com", "api.github.com"].contains(&host) {
+            return Ok(GithubApi {
+                base: GITHUB_API.to_string(),
+                template_name: "github_api_latest".to_string(),
+            });
+        }
+        let with_scheme = if host.contains("://") {
+            host.to_string()
+        } else {
+            format!("https://{host}")
+        };
+        let mut url = Url::parse(&with_scheme)
+            .ok()
+            .filter(|url| url.has_host())
+            .ok_or_else(|| anyhow!("invalid GitHub host {host:?}"))?;
+        if url.path() == "/" {
+            url.set_path("/api/v3");
+        }
+        let name = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        Ok(GithubApi {
+            base: url.as_str().trim_end_matches('/').to_string(),
+            template_name: format!("github_api_latest@{name}"),
+        })
+    }
+
+    fn template(&self) -> String {
+        format!(
+            "[template:{}]\nmethod = api_json\npage_url = {}/repos/{{project}}/releases/latest\nversion_tag = $.tag_name\nanchor_tag = $.assets.*.browser_download_url\ndigest_tag = $.assets.*.digest\n",
+            self.template_name, self.base
+        )
+    }
+}
 
 /// Fetch the latest GitHub release, infer the best asset for this host, and
-/// append a `github_api_latest` section to `config_path`.
+/// append a section using the `github_api_latest` template (or the one for
+/// the GitHub Enterprise host) to `config_path`.
 pub fn add_github_definition(
+    ctx: &RunContext,
     config_path: &Path,
     options: &AddGithubOptions,
 ) -> Result<AddedGithubDefinition> {
@@ -51,14 +110,17 @@ pub fn add_github_definition(
         bail!("config already contains section [{section_name}]; choose another name with --name");
     }
 
-    let release = fetch_latest_release(&options.repo)?;
-    let definition = definition_from_release(&options.repo, &release, options)?;
-    let needs_template = !has_section(&existing_config, "template:github_api_latest");
+    let api = GithubApi::for_host(options.host.as_deref())?;
+    let release = fetch_latest_release(ctx, &api, &options.repo)?;
+    let definition = definition_from_release(&options.repo, &release, &api, options)?;
+    let template_section = format!("template:{}", api.template_name);
+    let needs_template = !crate::templates::is_built_in(&api.template_name)
+        && !has_section(&existing_config, &template_section);
     if !options.dry_run {
         append_definition(
             config_path,
             &existing_config,
-            needs_template,
+            needs_template.then(|| api.template()).as_deref(),
             &definition.entry,
         )?;
     }
@@ -71,44 +133,35 @@ pub fn add_github_definition(
     })
 }
 
-fn fetch_latest_release(repo: &str) -> Result<GithubRelease> {
-    let url = format!("https://api.github.com/repos/{repo}/releases/latest");
-    let mut attempts_remaining = 3;
-    let response = loop {
-        if attempts_remaining == 0 {
-            bail!("failed to fetch latest release for {repo}");
-        }
-        attempts_remaining -= 1;
-
-        let agent = crate::http_agent();
-        let request = agent
-            .get(&url)
+fn fetch_latest_release(ctx: &RunContext, api: &GithubApi, repo: &str) -> Result<GithubRelease> {
+    let url = format!("{}/repos/{repo}/releases/latest", api.base);
+    let policy = &ctx.retry;
+    let body = policy.run(repo, &url, || {
+        let request = ctx
+            .credentials
+            .apply(&url, policy.apply(ctx.agent.get(&url)))
             .header("Accept", "application/vnd.github+json")
             .header("X-GitHub-Api-Version", "2022-11-28")
             .header("User-Agent", "lifter");
-        let request = if let Ok(token) = std::env::var("GITHUB_TOKEN") {
-            request.header("Authorization", &format!("Bearer {token}"))
-        } else {
-            request
-        };
 
-        let response = request
-            .call()
-            .map_err(|e| anyhow!("error fetching {url}: {e}"))?;
+        let response = match request.call() {
+            Ok(response) => response,
+            Err(e) => return policy.transport_error(&url, e),
+        };
         match response.status().as_u16() {
-            200..=299 => break response,
-            403 | 408 | 425 | 429 | 500 | 502 | 503 | 504 if attempts_remaining > 0 => {
-                let delay = Duration::from_secs((3 - attempts_remaining) * 2);
-                std::thread::sleep(delay);
+            200..=299 => match response.into_body().read_to_string() {
+                Ok(body) => Ok(Attempt::Done(body)),
+                Err(e) => policy.transport_error(&url, e),
+            },
+            status if policy.retries_status(status) => {
+                Ok(Attempt::Retry(format!("Got status {status}")))
             }
             status => {
                 let body = response.into_body().read_to_string()?;
                 bail!("GitHub returned status {status} for {url}: {body}");
             }
         }
-    };
-
-    let body = response.into_body().read_to_string()?;
+    })?;
     release_from_json(&body)
 }
 
@@ -139,6 +192,7 @@ fn release_from_json(payload: &str) -> Result<GithubRelease> {
 fn definition_from_release(
     repo: &str,
     release: &GithubRelease,
+    api: &GithubApi,
     options: &AddGithubOptions,
 ) -> Result<AddedGithubDefinition> {
     let asset_name = select_asset(&release.assets, options.asset_filter.as_deref())?;
@@ -146,6 +200,7 @@ fn definition_from_release(
     let anchor_text = anchor_text_for_asset(&asset_name, &release.tag_name);
     let entry = render_definition(
         &section_name,
+        &api.template_name,
         repo,
         &anchor_text,
         options.extract.as_deref(),
@@ -158,6 +213,7 @@ fn definition_from_release(
         asset_name,
         version: release.tag_name.clone(),
         entry,
+        template_name: api.template_name.clone(),
         wrote_template: false,
         wrote_file: false,
     })
@@ -232,18 +288,16 @@ fn asset_score(asset: &str) -> i32 {
     }
 
     let mut score = 0;
-    let arch_aliases = match std::env::consts::ARCH {
-        "x86_64" => vec!["x86_64", "amd64", "x64"],
-        "aarch64" => vec!["aarch64", "arm64"],
-        "arm" => vec!["armv7", "arm-", "arm_"],
-        "x86" => vec!["i686", "i386", "x86"],
-        other => vec![other],
-    };
-    if arch_aliases.iter().any(|alias| lower.contains(alias)) {
+    let platform = Platform::current();
+    if platform
+        .arch_aliases()
+        .iter()
+        .any(|alias| lower.contains(alias))
+    {
         score += 20;
     }
 
-    match std::env::consts::OS {
+    match platform.os.as_str() {
         "linux" => {
             if lower.contains("linux") {
                 score += 20;
@@ -387,6 +441,7 @@ fn escape_regex_literal(value: &str) -> String {
 
 fn render_definition(
     section_name: &str,
+    template_name: &str,
     repo: &str,
     anchor_text: &str,
     extract: Option<&str>,
@@ -396,14 +451,14 @@ fn render_definition(
         .map(|target| format!("target_filename_to_extract_from_archive = {target}\n"))
         .unwrap_or_default();
     format!(
-        "[{section_name}]\ntemplate = github_api_latest\nproject = {repo}\nanchor_text = {anchor_text}\n{extract_line}version = {version}\n"
+        "[{section_name}]\ntemplate = {template_name}\nproject = {repo}\nanchor_text = {anchor_text}\n{extract_line}version = {version}\n"
     )
 }
 
 fn append_definition(
     config_path: &Path,
     existing_config: &str,
-    needs_template: bool,
+    template: Option<&str>,
     entry: &str,
 ) -> Result<()> {
     let mut file = fs::OpenOptions::new()
@@ -417,8 +472,8 @@ fn append_definition(
     if !existing_config.trim().is_empty() {
         writeln!(file)?;
     }
-    if needs_template {
-        writeln!(file, "{GITHUB_API_TEMPLATE}")?;
+    if let Some(template) = template {
+        writeln!(file, "{template}")?;
     }
     writeln!(file, "{entry}")?;
     Ok(())
@@ -449,6 +504,7 @@ mod tests {
     fn options(repo: &str) -> AddGithubOptions {
         AddGithubOptions {
             repo: repo.to_string(),
+            host: None,
             section_name: None,
             asset_filter: None,
             extract: None,
@@ -456,6 +512,10 @@ mod tests {
         }
     }
 
+    fn github() -> GithubApi {
+        GithubApi::for_host(None).unwrap()
+    }
+
     #[test]
     fn renders_starship_style_definition_without_version_in_asset_name() -> Result<()> {
         let release = GithubRelease {
@@ -463,8 +523,12 @@ mod tests {
             assets: vec!["starship-x86_64-unknown-linux-musl.tar.gz".to_string()],
         };
 
-        let definition =
-            definition_from_release("starship/starship", &release, &options("starship/starship"))?;
+        let definition = definition_from_release(
+            "starship/starship",
+            &release,
+            &github(),
+            &options("starship/starship"),
+        )?;
 
         assert_eq!(definition.section_name, "starship");
         assert_eq!(
@@ -483,7 +547,8 @@ mod tests {
         let mut options = options("BurntSushi/ripgrep");
         options.extract = Some("rg".to_string());
 
-        let definition = definition_from_release("BurntSushi/ripgrep", &release, &options)?;
+        let definition =
+            definition_from_release("BurntSushi/ripgrep", &release, &github(), &options)?;
 
         assert_eq!(
             definition.entry,
@@ -514,4 +579,23 @@ mod tests {
         assert!(has_section(config, "ripgrep"));
         assert!(!has_section(config, "starship"));
     }
+
+    #[test]
+    fn enterprise_hosts_get_their_own_api_and_template() -> Result<()> {
+        let api = GithubApi::for_host(Some("github.example.com"))?;
+        assert_eq!(api.base, "https://github.example.com/api/v3");
+        assert_eq!(api.template_name, "github_api_latest@github.example.com");
+        assert!(api.template().starts_with(
+            "[template:github_api_latest@github.example.com]\nmethod = api_json\n\
+             page_url = https://github.example.com/api/v3/repos/{project}/releases/latest\n"
+        ));
+
+        let api = GithubApi::for_host(Some("http://ghe.internal:8080/custom/api/"))?;
+        assert_eq!(api.base, "http://ghe.internal:8080/custom/api");
+        assert_eq!(api.template_name, "github_api_latest@ghe.internal:8080");
+
+        assert_eq!(GithubApi::for_host(Some("github.com"))?.base, GITHUB_API);
+        assert!(GithubApi::for_host(Some("not a host")).is_err());
+        Ok(())
+    }
 }
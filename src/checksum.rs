@@ -0,0 +1,296 @@
+//! SHA-256 verification of downloaded artifacts against published
+//! checksum files.
+//!
+//! Projects publish digests in a handful of shapes, all handled by
+//! `find_digest`:
+//!
+//! ```text
+//! e3b0c442...b855  ripgrep-14.1.1-x86_64-unknown-linux-musl.tar.gz   # sha256sum
+//! e3b0c442...b855 *ripgrep-14.1.1-x86_64-unknown-linux-musl.tar.gz   # sha256sum -b
+//! SHA256 (ripgrep-14.1.1-x86_64-unknown-linux-musl.tar.gz) = e3b0...  # BSD / shasum --tag
+//! e3b0c442...b855                                                     # lone digest (*.sha256)
+//! ```
+//!
+//! A checksum file either comes from the section config
+//! (`checksum_url`, or `checksum_anchor_text` matched against the same
+//! links as `anchor_text`), or is discovered among the release assets
+//! of an `api_json` response by `discover`.
+
+use std::io::Read;
+
+use sha2::{Digest, Sha256};
+
+/// Result of checking a downloaded artifact against a checksum file.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Verification {
+    Verified,
+    Mismatch {
+        expected: String,
+        actual: String,
+    },
+    /// The checksum file has no entry for this artifact.
+    Unlisted,
+}
+
+/// Passes reads through while hashing them, so a download is hashed
+/// as it streams to disk rather than read back afterwards.
+pub(crate) struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    len: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        HashingReader {
+            inner,
+            hasher: Sha256::new(),
+            len: 0,
+        }
+    }
+
+    /// Hash `prefix` as though it had been read through `self` first,
+    /// e.g. the part of a download already on disk when resuming it.
+    pub(crate) fn absorb(&mut self, mut prefix: impl Read) -> std::io::Result<()> {
+        let mut buf = [0u8; 64 * 1024];
+        loop {
+            let n = prefix.read(&mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            self.hasher.update(&buf[..n]);
+            self.len += n as u64;
+        }
+    }
+
+    /// Lowercase hex SHA-256 and length of everything read so far.
+    pub(crate) fn finish(self) -> (String, u64) {
+        let hex = self
+            .hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        (hex, self.len)
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+}
+
+/// The final path segment of `url`, which is what checksum files list.
+pub(crate) fn asset_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn is_sha256_hex(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Hex digest from a `sha256:<hex>` value, as GitHub reports for each
+/// release asset. A bare hex digest is accepted too; other algorithms
+/// yield `None`.
+pub(crate) fn parse_digest(value: &str) -> Option<String> {
+    let value = value.trim();
+    let hex = value.strip_prefix("sha256:").unwrap_or(value);
+    is_sha256_hex(hex).then(|| hex.to_ascii_lowercase())
+}
+
+/// Find the digest recorded for `asset` in the body of a checksum file.
+/// Entry names are compared by basename, so `./dist/foo.tar.gz` matches
+/// `foo.tar.gz`. A file holding nothing but a single digest is taken to
+/// describe `asset` — that's the per-asset `foo.tar.gz.sha256` style.
+pub(crate) fn find_digest(body: &str, asset: &str) -> Option<String> {
+    let lines: Vec<&str> = body
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect();
+
+    for line in &lines {
+        // BSD style: `SHA256 (name) = digest`
+        if let Some(rest) = line.strip_prefix("SHA256 (") {
+            if let Some((name, digest)) = rest.split_once(") = ") {
+                if asset_name(name) == asset && is_sha256_hex(digest.trim()) {
+                    return Some(digest.trim().to_ascii_lowercase());
+                }
+            }
+            continue;
+        }
+        // GNU style: `digest  name` or `digest *name`
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let (Some(digest), Some(name)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let name = name.trim().trim_start_matches('*');
+        if is_sha256_hex(digest) && asset_name(name) == asset {
+            return Some(digest.to_ascii_lowercase());
+        }
+    }
+
+    match lines.as_slice() {
+        [only] if is_sha256_hex(only) => Some(only.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+/// Pick a checksum file for `download_url` from the other links in the
+/// same release. A per-asset digest file (`<asset>.sha256`) wins over a
+/// combined one (`SHA256SUMS`, `checksums.txt`, `foo_1.0_checksums.txt`).
+pub(crate) fn discover(candidates: &[String], download_url: &str) -> Option<String> {
+    let asset = asset_name(download_url).to_ascii_lowercase();
+    let per_asset = [format!("{asset}.sha256"), format!("{asset}.sha256sum")];
+    let combined = |name: &str| {
+        matches!(
+            name,
+            "sha256sums" | "sha256sums.txt" | "sha256sum.txt" | "checksums.txt"
+        ) || name.ends_with("_checksums.txt")
+            || name.ends_with("-checksums.txt")
+            || name.ends_with("_sha256sums.txt")
+            || name.ends_with("-sha256sums.txt")
+    };
+
+    let lower = |u: &String| asset_name(u).to_ascii_lowercase();
+    candidates
+        .iter()
+        .find(|u| per_asset.contains(&lower(u)))
+        .or_else(|| candidates.iter().find(|u| combined(&lower(u))))
+        .cloned()
+}
+
+/// Compare the artifact's hex SHA-256, `actual`, with the entry for
+/// `download_url` in the checksum file body.
+pub(crate) fn verify(actual: &str, download_url: &str, checksums: &str) -> Verification {
+    let Some(expected) = find_digest(checksums, asset_name(download_url)) else {
+        return Verification::Unlisted;
+    };
+    if actual == expected {
+        Verification::Verified
+    } else {
+        Verification::Mismatch {
+            expected,
+            actual: actual.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+    const URL: &str = "https://example.com/releases/download/1.0/tool-1.0-linux.tar.gz";
+
+    fn sha256_hex(bytes: &[u8]) -> String {
+        let mut reader = HashingReader::new(bytes);
+        std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+        reader.finish().0
+    }
+
+    #[test]
+    fn sha256_of_empty_input() {
+        assert_eq!(sha256_hex(b""), EMPTY);
+    }
+
+    #[test]
+    fn hashing_reader_counts_and_hashes_what_passes_through() {
+        let data = vec![7u8; 100_000];
+        let mut reader = HashingReader::new(&data[..]);
+        let mut out = Vec::new();
+        std::io::copy(&mut reader, &mut out).unwrap();
+        assert_eq!(out, data);
+        let (hex, len) = reader.finish();
+        assert_eq!(hex, format!("{:x}", Sha256::digest(&data)));
+        assert_eq!(len, 100_000);
+    }
+
+    #[test]
+    fn absorbed_prefix_counts_towards_the_digest() {
+        let mut reader = HashingReader::new(&b"world"[..]);
+        reader.absorb(&b"hello "[..]).unwrap();
+        std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+        assert_eq!(reader.finish(), (sha256_hex(b"hello world"), 11));
+    }
+
+    #[test]
+    fn gnu_style_lines() {
+        let body = format!(
+            "{}  other.zip\n{} *./dist/tool-1.0-linux.tar.gz\n",
+            "0".repeat(64),
+            EMPTY
+        );
+        assert_eq!(
+            find_digest(&body, "tool-1.0-linux.tar.gz").as_deref(),
+            Some(EMPTY)
+        );
+    }
+
+    #[test]
+    fn bsd_style_line() {
+        let body = format!(
+            "SHA256 (tool-1.0-linux.tar.gz) = {}\n",
+            EMPTY.to_uppercase()
+        );
+        assert_eq!(
+            find_digest(&body, "tool-1.0-linux.tar.gz").as_deref(),
+            Some(EMPTY)
+        );
+    }
+
+    #[test]
+    fn lone_digest_applies_to_the_asset() {
+        assert_eq!(find_digest(EMPTY, "anything").as_deref(), Some(EMPTY));
+        assert_eq!(find_digest("not-a-digest\n", "anything"), None);
+    }
+
+    #[test]
+    fn verify_reports_each_outcome() {
+        let listed = format!("{}  tool-1.0-linux.tar.gz\n", EMPTY);
+        assert_eq!(verify(EMPTY, URL, &listed), Verification::Verified);
+        assert!(matches!(
+            verify(&sha256_hex(b"tampered"), URL, &listed),
+            Verification::Mismatch { .. }
+        ));
+        let unlisted = format!("{}  other.zip\n{}  another.zip\n", EMPTY, EMPTY);
+        assert_eq!(verify(EMPTY, URL, &unlisted), Verification::Unlisted);
+    }
+
+    #[test]
+    fn parses_github_asset_digest() {
+        let github = format!("sha256:{}", EMPTY);
+        assert_eq!(parse_digest(&github).as_deref(), Some(EMPTY));
+        assert_eq!(parse_digest(EMPTY).as_deref(), Some(EMPTY));
+        assert_eq!(parse_digest("sha512:abcd"), None);
+    }
+
+    #[test]
+    fn discover_prefers_per_asset_digest() {
+        let urls = vec![
+            "https://example.com/d/1.0/checksums.txt".to_string(),
+            "https://example.com/d/1.0/tool-1.0-linux.tar.gz".to_string(),
+            "https://example.com/d/1.0/tool-1.0-linux.tar.gz.sha256".to_string(),
+        ];
+        assert_eq!(
+            discover(&urls, URL).as_deref(),
+            Some("https://example.com/d/1.0/tool-1.0-linux.tar.gz.sha256")
+        );
+        assert_eq!(
+            discover(&urls[..2], URL).as_deref(),
+            Some("https://example.com/d/1.0/checksums.txt")
+        );
+    }
+
+    #[test]
+    fn discover_matches_goreleaser_names() {
+        let urls = vec!["https://example.com/d/fzf_0.60.3_checksums.txt".to_string()];
+        assert!(discover(&urls, URL).is_some());
+        assert!(discover(&[], URL).is_none());
+    }
+}
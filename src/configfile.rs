@@ -0,0 +1,320 @@
+//! In-place edits of `lifter.config`.
+//!
+//! tini can only write a config by serializing the whole thing, which
+//! drops comments and blank lines and normalizes spacing. After each
+//! update the only thing that should change is the `version` (and
+//! `commit`) line of the section that updated, so this works on the
+//! raw text instead, line by line, recognizing lines the same way
+//! tini's parser does:
+//!
+//!   * anything after `;` or `#` is a comment;
+//!   * `[name]` starts a section;
+//!   * `key = value` is an item.
+//!
+//! An existing item keeps its key spelling, spacing around `=` and any
+//! trailing comment; only the value changes. A missing item is added
+//! after the last item of its section, so it doesn't land below a
+//! comment that introduces the next section. Line endings (`\n` or
+//! `\r\n`) follow the file.
+//!
+//! Writes are atomic: the new text goes to a temporary file beside the
+//! config, is fsynced, and is renamed over the original, so a crash or
+//! a full disk leaves either the old config or the new one, never a
+//! truncated mix. The previous version is kept as `<config>.bak`.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+/// Set each `(key, value)` in `section` of the config at `path`,
+/// leaving every other line as it was.
+pub(crate) fn update_section(path: &Path, section: &str, items: &[(&str, &str)]) -> Result<()> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let updated = set_items(&text, section, items);
+    if updated != text {
+        let backup = sibling(path, ".bak");
+        fs::copy(path, &backup)
+            .map_err(|e| anyhow!("Failed to back up {}: {}", path.display(), e))?;
+        write_atomic(path, updated.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Replace the file at `path` with `contents` via a fsynced temporary
+/// file and a rename. The file's permissions are carried over.
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = sibling(path, &format!(".tmp-{}", std::process::id()));
+    let result = (|| -> std::io::Result<()> {
+        let mut file = File::create(&tmp)?;
+        if let Ok(meta) = fs::metadata(path) {
+            file.set_permissions(meta.permissions())?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        sync_parent_dir(path)
+    })();
+    result.map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        anyhow!("Failed to write {}: {}", path.display(), e)
+    })
+}
+
+/// `path` with `suffix` appended to its file name.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Make the rename itself durable. Directories can't be opened for
+/// syncing on Windows, where the rename is already durable enough.
+#[cfg(target_family = "unix")]
+fn sync_parent_dir(path: &Path) -> std::io::Result<()> {
+    match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(dir) => File::open(dir)?.sync_all(),
+        None => File::open(".")?.sync_all(),
+    }
+}
+#[cfg(not(target_family = "unix"))]
+fn sync_parent_dir(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// The body of a line with its comment and surrounding whitespace
+/// removed, as tini sees it.
+fn content(line: &str) -> &str {
+    line.split([';', '#']).next().unwrap_or("").trim()
+}
+
+fn section_name(line: &str) -> Option<&str> {
+    let c = content(line);
+    (c.starts_with('[') && c.ends_with(']')).then(|| c.trim_matches(|ch| ch == '[' || ch == ']'))
+}
+
+fn item_key(line: &str) -> Option<&str> {
+    content(line)
+        .split_once('=')
+        .map(|(k, _)| k.trim())
+        .filter(|k| !k.is_empty())
+}
+
+/// Replace the value of an item line, keeping everything around it.
+fn replace_value(line: &str, value: &str) -> String {
+    let eq = line.find('=').expect("item line contains '='");
+    let after_eq = &line[eq + 1..];
+    let comment_start = after_eq.find([';', '#']).unwrap_or(after_eq.len());
+    let old = &after_eq[..comment_start];
+    let lead = &old[..old.len() - old.trim_start().len()];
+    let trail = &old[old.trim_end().len()..];
+    // `key =` with no value yet gets the conventional single space.
+    let lead = if old.trim().is_empty() && lead.is_empty() {
+        " "
+    } else {
+        lead
+    };
+    format!(
+        "{}{}{}{}{}",
+        &line[..=eq],
+        lead,
+        value,
+        trail,
+        &after_eq[comment_start..]
+    )
+}
+
+/// Apply the edits to the text of a config. Pure, for testing.
+fn set_items(text: &str, section: &str, items: &[(&str, &str)]) -> String {
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    // Each line keeps its own terminator (if any).
+    let mut lines: Vec<String> = text.split_inclusive('\n').map(String::from).collect();
+
+    let Some(header) = lines.iter().position(|l| section_name(l) == Some(section)) else {
+        // The section isn't in the file at all: append it.
+        let mut out = text.to_string();
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push_str(newline);
+        }
+        if !out.trim().is_empty() {
+            out.push_str(newline);
+        }
+        out.push_str(&format!("[{}]{}", section, newline));
+        for (key, value) in items {
+            out.push_str(&format!("{} = {}{}", key, value, newline));
+        }
+        return out;
+    };
+    let end = lines[header + 1..]
+        .iter()
+        .position(|l| section_name(l).is_some())
+        .map_or(lines.len(), |i| header + 1 + i);
+
+    let mut missing = Vec::new();
+    for (key, value) in items {
+        match (header + 1..end).find(|&i| item_key(&lines[i]) == Some(key)) {
+            Some(i) => {
+                let (body, ending) = split_ending(&lines[i]);
+                lines[i] = format!("{}{}", replace_value(body, value), ending);
+            }
+            None => missing.push(format!("{} = {}{}", key, value, newline)),
+        }
+    }
+
+    if !missing.is_empty() {
+        let last_item = (header + 1..end)
+            .rev()
+            .find(|&i| item_key(&lines[i]).is_some())
+            .unwrap_or(header);
+        if !lines[last_item].ends_with('\n') {
+            lines[last_item].push_str(newline);
+        }
+        lines.splice(last_item + 1..last_item + 1, missing);
+    }
+
+    lines.concat()
+}
+
+fn split_ending(line: &str) -> (&str, &str) {
+    let body = line.trim_end_matches(['\r', '\n']);
+    (body, &line[body.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+# Tools for the team. Keep sorted.
+[template:github_api_latest]
+method = api_json
+version_tag = $.tag_name
+
+[ripgrep]
+template = github_api_latest
+project = BurntSushi/ripgrep
+version   =   13.0.0   ; bumped by lifter
+
+# Neovim's \"stable\" tag moves, so track the commit too.
+[nvim]
+template = github_api_latest
+version = stable
+
+[fzf]
+version = v0.50.0
+";
+
+    #[test]
+    fn only_the_changed_line_differs() {
+        let out = set_items(CONFIG, "ripgrep", &[("version", "14.1.1")]);
+        assert_eq!(
+            out,
+            CONFIG.replace(
+                "version   =   13.0.0   ; bumped",
+                "version   =   14.1.1   ; bumped"
+            )
+        );
+    }
+
+    #[test]
+    fn missing_key_goes_after_the_last_item() {
+        let out = set_items(
+            CONFIG,
+            "nvim",
+            &[("version", "stable"), ("commit", "abc123")],
+        );
+        assert_eq!(
+            out,
+            CONFIG.replace("version = stable\n", "version = stable\ncommit = abc123\n")
+        );
+    }
+
+    #[test]
+    fn missing_key_does_not_land_below_next_sections_comment() {
+        let out = set_items(CONFIG, "ripgrep", &[("commit", "abc123")]);
+        assert!(out.contains("; bumped by lifter\ncommit = abc123\n\n# Neovim's"));
+    }
+
+    #[test]
+    fn last_section_without_trailing_newline() {
+        let text = "[fzf]\nversion = v0.50.0";
+        assert_eq!(
+            set_items(text, "fzf", &[("version", "v0.60.3"), ("commit", "abc")]),
+            "[fzf]\nversion = v0.60.3\ncommit = abc\n"
+        );
+    }
+
+    #[test]
+    fn crlf_line_endings_are_kept() {
+        let text = "[fzf]\r\nproject = junegunn/fzf\r\n";
+        assert_eq!(
+            set_items(text, "fzf", &[("version", "v0.60.3")]),
+            "[fzf]\r\nproject = junegunn/fzf\r\nversion = v0.60.3\r\n"
+        );
+    }
+
+    #[test]
+    fn absent_section_is_appended() {
+        assert_eq!(
+            set_items("[fzf]\nversion = 1\n", "bat", &[("version", "2")]),
+            "[fzf]\nversion = 1\n\n[bat]\nversion = 2\n"
+        );
+    }
+
+    #[test]
+    fn keys_in_other_sections_are_untouched() {
+        let out = set_items(CONFIG, "fzf", &[("version", "v0.60.3")]);
+        assert!(out.contains("[nvim]\ntemplate = github_api_latest\nversion = stable\n"));
+        assert!(out.ends_with("[fzf]\nversion = v0.60.3\n"));
+    }
+
+    #[test]
+    fn value_directly_followed_by_comment() {
+        assert_eq!(replace_value("version=1.0;old", "2.0"), "version=2.0;old");
+        assert_eq!(replace_value("version =", "2.0"), "version = 2.0");
+    }
+
+    #[test]
+    fn update_section_keeps_a_backup() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("lifter.config");
+        fs::write(&path, CONFIG)?;
+
+        update_section(&path, "fzf", &[("version", "v0.60.3")])?;
+
+        assert!(fs::read_to_string(&path)?.ends_with("version = v0.60.3\n"));
+        assert_eq!(fs::read_to_string(sibling(&path, ".bak"))?, CONFIG);
+        // Nothing left behind but the config and its backup.
+        assert_eq!(fs::read_dir(dir.path())?.count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn failed_write_leaves_the_config_alone() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let missing_dir = dir.path().join("gone").join("lifter.config");
+        assert!(write_atomic(&missing_dir, b"[fzf]\n").is_err());
+        assert_eq!(fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn result_parses_back_with_tini() {
+        let out = set_items(
+            CONFIG,
+            "nvim",
+            &[("version", "stable"), ("commit", "abc123")],
+        );
+        let ini = tini::Ini::from_string(out).unwrap();
+        assert_eq!(
+            ini.get::<String>("nvim", "commit").as_deref(),
+            Some("abc123")
+        );
+        assert_eq!(
+            ini.get::<String>("ripgrep", "version").as_deref(),
+            Some("13.0.0")
+        );
+    }
+}
@@ -0,0 +1,505 @@
+//! Credentials for the hosts lifter talks to.
+//!
+//! Every request, whether for a release page, an API, a checksum file or
+//! the artifact itself, is sent with credentials for the host in its
+//! URL, if there are any. Nothing is sent to other hosts. A download
+//! redirected from the host that was asked to another, such as GitHub's
+//! release downloads ending up on its CDN, loses the `Authorization`
+//! header on the way.
+//!
+//! A host's credentials come from the first source that has some:
+//!
+//! 1. a `[credentials:<host>]` section in the config:
+//!
+//!    ```ini
+//!    [credentials:api.github.com]
+//!    token_command = gh auth token
+//!
+//!    [credentials:gitlab.example.com]
+//!    token_file = ~/.config/lifter/gitlab-token
+//!
+//!    [credentials:gitea.example.org]
+//!    token_env = GITEA_TOKEN
+//!
+//!    [credentials:artifacts.example.com]
+//!    username = ci
+//!    token_env = ARTIFACTS_PASSWORD
+//!    ```
+//!
+//!    Within a section, `token_env`, `token_file`, `token_command` and
+//!    `token` are tried in that order. A token is sent as
+//!    `Authorization: Bearer <token>`, which GitHub, GitLab and Gitea
+//!    all accept, or with HTTP Basic auth if a `username` is given;
+//! 2. the usual environment variable of a well-known host:
+//!    `GITHUB_TOKEN` (or `GH_TOKEN`) for `api.github.com` and
+//!    `GITLAB_TOKEN` for `gitlab.com`;
+//! 3. a `machine` entry for the host in `~/.netrc` (or the file named by
+//!    `NETRC`), sent with HTTP Basic auth. The `default` entry is
+//!    ignored: it would hand the same password to every host.
+//!
+//! Each host's credentials are looked up once per run, so a credential
+//! command runs at most once however many sections use the host.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use log::*;
+use ureq::typestate::WithoutBody;
+use ureq::RequestBuilder;
+use url::Url;
+
+/// Config sections named `credentials:<host>` hold that host's
+/// credentials.
+pub const CREDENTIALS_PREFIX: &str = "credentials:";
+
+/// Hosts whose tokens are conventionally kept in environment variables.
+const WELL_KNOWN_HOSTS: [(&str, &[&str]); 2] = [
+    ("api.github.com", &["GITHUB_TOKEN", "GH_TOKEN"]),
+    ("gitlab.com", &["GITLAB_TOKEN"]),
+];
+
+/// Somewhere a token can be found.
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    Env(String),
+    File(PathBuf),
+    Command(String),
+    Literal(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct HostConfig {
+    username: Option<String>,
+    sources: Vec<Source>,
+}
+
+#[derive(Clone, PartialEq)]
+enum Credential {
+    Bearer(String),
+    Basic { username: String, password: String },
+}
+
+impl Credential {
+    fn header_value(&self) -> String {
+        match self {
+            Credential::Bearer(token) => format!("Bearer {token}"),
+            Credential::Basic { username, password } => {
+                let pair = format!("{username}:{password}");
+                let encoded = base64::engine::general_purpose::STANDARD.encode(pair);
+                format!("Basic {encoded}")
+            }
+        }
+    }
+}
+
+/// The credential chain for a run. The default has no sources at all.
+#[derive(Default)]
+pub struct Credentials {
+    hosts: HashMap<String, HostConfig>,
+    netrc: Option<PathBuf>,
+    /// Looked up so far, or being looked up, by host.
+    resolved: Mutex<HashMap<String, Arc<OnceLock<Option<Credential>>>>>,
+}
+
+impl Credentials {
+    /// Well-known environment variables and `~/.netrc`, for when there
+    /// is no config to read.
+    pub fn from_environment() -> Self {
+        let hosts = WELL_KNOWN_HOSTS
+            .iter()
+            .map(|(host, vars)| {
+                let config = HostConfig {
+                    username: None,
+                    sources: vars.iter().map(|v| Source::Env(v.to_string())).collect(),
+                };
+                (host.to_string(), config)
+            })
+            .collect();
+        let netrc = std::env::var_os("NETRC")
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".netrc")));
+        Credentials {
+            hosts,
+            netrc,
+            resolved: Mutex::default(),
+        }
+    }
+
+    /// The `[credentials:<host>]` sections of the config at
+    /// `config_path`, ahead of the environment. Relative token files
+    /// are relative to the config file.
+    pub fn from_config(conf: &tini::Ini, config_path: &Path) -> Result<Self> {
+        let mut credentials = Self::from_environment();
+        let config_dir = config_path.parent().unwrap_or(Path::new(""));
+        for (name, section) in conf.iter() {
+            let Some(host) = name.strip_prefix(CREDENTIALS_PREFIX) else {
+                continue;
+            };
+            let values: HashMap<&str, &str> = section
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect();
+            let mut sources = Vec::new();
+            if let Some(var) = values.get("token_env") {
+                sources.push(Source::Env(var.trim().to_string()));
+            }
+            if let Some(path) = values.get("token_file") {
+                sources.push(Source::File(config_dir.join(expand_home(path.trim()))));
+            }
+            if let Some(command) = values.get("token_command") {
+                sources.push(Source::Command(command.to_string()));
+            }
+            if let Some(token) = values.get("token") {
+                sources.push(Source::Literal(token.trim().to_string()));
+            }
+            if sources.is_empty() {
+                return Err(anyhow!(
+                    "[{}] Needs one of token_env, token_file, token_command or token",
+                    name
+                ));
+            }
+            let host = host.trim().to_ascii_lowercase();
+            let username = values.get("username").map(|u| u.trim().to_string());
+            // Replaces the environment variables of a well-known host.
+            credentials
+                .hosts
+                .insert(host, HostConfig { username, sources });
+        }
+        Ok(credentials)
+    }
+
+    /// Add the credentials for the host of `url`, if it has any, to
+    /// `request`.
+    pub(crate) fn apply(
+        &self,
+        url: &str,
+        request: RequestBuilder<WithoutBody>,
+    ) -> RequestBuilder<WithoutBody> {
+        match self.for_url(url) {
+            Some(credential) => request.header("Authorization", &credential.header_value()),
+            None => request,
+        }
+    }
+
+    fn for_url(&self, url: &str) -> Option<Credential> {
+        let host = Url::parse(url).ok()?.host_str()?.to_ascii_lowercase();
+        let cell = self
+            .resolved
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(host.clone())
+            .or_default()
+            .clone();
+        // Workers that need the same host at the same time wait here, so
+        // a credential command runs only once; other hosts don't wait.
+        cell.get_or_init(|| self.look_up(&host)).clone()
+    }
+
+    fn look_up(&self, host: &str) -> Option<Credential> {
+        if let Some(config) = self.hosts.get(host) {
+            for source in &config.sources {
+                if let Some(token) = read_token(host, source) {
+                    debug!(
+                        "Using credentials from {} for {}",
+                        source_name(source),
+                        host
+                    );
+                    return Some(match &config.username {
+                        Some(username) => Credential::Basic {
+                            username: username.clone(),
+                            password: token,
+                        },
+                        None => Credential::Bearer(token),
+                    });
+                }
+            }
+        }
+        let netrc = self.netrc.as_ref()?;
+        let text = std::fs::read_to_string(netrc).ok()?;
+        let (username, password) = netrc_entry(&text, host)?;
+        debug!("Using credentials from {} for {}", netrc.display(), host);
+        Some(Credential::Basic { username, password })
+    }
+}
+
+/// How a source is described in logs, without giving the token away.
+fn source_name(source: &Source) -> String {
+    match source {
+        Source::Env(var) => format!("${var}"),
+        Source::File(path) => path.display().to_string(),
+        Source::Command(command) => command.clone(),
+        Source::Literal(_) => "the config".to_string(),
+    }
+}
+
+fn read_token(host: &str, source: &Source) -> Option<String> {
+    let token = match source {
+        Source::Env(var) => std::env::var(var).ok()?,
+        Source::Literal(token) => token.clone(),
+        Source::File(path) => match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                warn!(
+                    "Can't read token file {} for {}: {}",
+                    path.display(),
+                    host,
+                    e
+                );
+                return None;
+            }
+        },
+        Source::Command(command) => match run_command(command) {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("Credential command for {} failed: {}", host, e);
+                return None;
+            }
+        },
+    };
+    let token = token.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+fn run_command(command: &str) -> Result<String> {
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(["/C", command]).output()
+    } else {
+        Command::new("sh").args(["-c", command]).output()
+    }
+    .map_err(|e| anyhow!("{}: {}", command, e))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{}: {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The login and password of the `machine` entry for `host`.
+fn netrc_entry(text: &str, host: &str) -> Option<(String, String)> {
+    let mut words = Vec::new();
+    let mut in_macro = false;
+    for line in text.lines() {
+        // A macro definition runs until the next blank line.
+        if in_macro {
+            in_macro = !line.trim().is_empty();
+            continue;
+        }
+        for token in line.split_whitespace() {
+            if token == "macdef" {
+                in_macro = true;
+                break;
+            }
+            words.push(token);
+        }
+    }
+
+    let mut words = words.into_iter();
+    let mut in_entry = false;
+    let (mut login, mut password) = (None, None);
+    while let Some(word) = words.next() {
+        match word {
+            "machine" | "default" if in_entry => break,
+            "machine" => in_entry = words.next()?.eq_ignore_ascii_case(host),
+            "login" if in_entry => login = words.next(),
+            "password" if in_entry => password = words.next(),
+            _ => {}
+        }
+    }
+    Some((login.unwrap_or_default().to_string(), password?.to_string()))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// `path` with a leading `~/` replaced by the home directory.
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::http_stand_in;
+    use std::time::{Duration, Instant};
+
+    fn from_config(text: &str, config_path: &Path) -> Credentials {
+        let conf = tini::Ini::from_string(text).unwrap();
+        let mut credentials = Credentials::from_config(&conf, config_path).unwrap();
+        // Whatever is in the real environment stays out of the tests.
+        credentials.hosts.retain(|host, _| {
+            conf.iter()
+                .any(|(name, _)| name.strip_prefix(CREDENTIALS_PREFIX) == Some(host.as_str()))
+        });
+        credentials.netrc = None;
+        credentials
+    }
+
+    fn header(credentials: &Credentials, url: &str) -> Option<String> {
+        credentials.for_url(url).map(|c| c.header_value())
+    }
+
+    #[test]
+    fn netrc_entries_match_their_machine_only() {
+        let netrc = "\
+machine gitea.example.org login ci password s3cret
+macdef init
+machine gitlab.example.com password nope
+
+machine gitlab.example.com
+    login oauth2
+    password glpat-123
+default login anonymous password guest
+";
+        assert_eq!(
+            netrc_entry(netrc, "gitea.example.org"),
+            Some(("ci".to_string(), "s3cret".to_string()))
+        );
+        assert_eq!(
+            netrc_entry(netrc, "gitlab.example.com"),
+            Some(("oauth2".to_string(), "glpat-123".to_string()))
+        );
+        assert_eq!(netrc_entry(netrc, "example.com"), None);
+    }
+
+    #[test]
+    fn config_sources_are_tried_in_order() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("gitlab-token"), "glpat-from-file\n")?;
+        let credentials = from_config(
+            "[credentials:gitlab.example.com]\n\
+             token_env = LIFTER_TEST_UNSET_TOKEN\n\
+             token_file = gitlab-token\n\
+             token = glpat-literal\n\
+             [credentials:artifacts.example.com]\n\
+             username = ci\n\
+             token = s3cret\n",
+            &dir.path().join("lifter.config"),
+        );
+        assert_eq!(
+            header(&credentials, "https://gitlab.example.com/api/v4/projects/1"),
+            Some("Bearer glpat-from-file".to_string())
+        );
+        assert_eq!(
+            header(&credentials, "https://artifacts.example.com/tool.zip"),
+            Some("Basic Y2k6czNjcmV0".to_string())
+        );
+        assert_eq!(header(&credentials, "https://example.com/"), None);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn credential_command_runs_once_per_host() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let count = dir.path().join("count");
+        let credentials = from_config(
+            &format!(
+                "[credentials:api.github.com]\n\
+                 token_command = echo run >> {} && echo gho_token\n",
+                count.display()
+            ),
+            &dir.path().join("lifter.config"),
+        );
+        std::thread::scope(|scope| {
+            for _ in 0..3 {
+                scope.spawn(|| {
+                    assert_eq!(
+                        header(
+                            &credentials,
+                            "https://api.github.com/repos/o/r/releases/latest"
+                        ),
+                        Some("Bearer gho_token".to_string())
+                    );
+                });
+            }
+        });
+        assert_eq!(std::fs::read_to_string(count)?, "run\n");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn slow_lookup_holds_up_only_its_own_host() -> Result<()> {
+        let credentials = from_config(
+            "[credentials:slow.example.com]\n\
+             token_command = sleep 1 && echo slow\n\
+             [credentials:fast.example.com]\n\
+             token = fast\n",
+            Path::new("lifter.config"),
+        );
+        std::thread::scope(|scope| {
+            let slow = scope.spawn(|| header(&credentials, "https://slow.example.com/"));
+            std::thread::sleep(Duration::from_millis(200));
+            let started = Instant::now();
+            assert_eq!(
+                header(&credentials, "https://fast.example.com/"),
+                Some("Bearer fast".to_string())
+            );
+            assert!(started.elapsed() < Duration::from_millis(500));
+            assert_eq!(slow.join().unwrap(), Some("Bearer slow".to_string()));
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn sections_need_a_source() {
+        let conf = tini::Ini::from_string("[credentials:gitea.example.org]\nusername = ci\n");
+        let err = Credentials::from_config(&conf.unwrap(), Path::new("lifter.config"))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.starts_with("[credentials:gitea.example.org]"), "{err}");
+    }
+
+    #[test]
+    fn redirects_to_another_host_lose_the_credentials() -> Result<()> {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        // Same server, but `localhost` is a different host from `127.0.0.1`.
+        let cdn = http_stand_in(move |head| {
+            log.lock().unwrap().push(head.to_ascii_lowercase());
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok".to_vec()
+        });
+        let cdn = cdn.replace("127.0.0.1", "localhost");
+        let log = seen.clone();
+        let origin = http_stand_in(move |head| {
+            log.lock().unwrap().push(head.to_ascii_lowercase());
+            format!("HTTP/1.1 302 Found\r\nLocation: {cdn}/tool.zip\r\nConnection: close\r\n\r\n")
+                .into_bytes()
+        });
+        let credentials = from_config(
+            "[credentials:127.0.0.1]\ntoken = secret\n",
+            Path::new("lifter.config"),
+        );
+        let url = format!("{origin}/download/tool.zip");
+        let body = credentials
+            .apply(&url, crate::http_agent().get(&url))
+            .call()?
+            .into_body()
+            .read_to_string()?;
+        assert_eq!(body, "ok");
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 2);
+        assert!(
+            seen[0].contains("authorization: bearer secret"),
+            "{}",
+            seen[0]
+        );
+        assert!(!seen[1].contains("authorization"), "{}", seen[1]);
+        Ok(())
+    }
+}
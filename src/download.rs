@@ -0,0 +1,454 @@
+//! Streaming, resumable artifact downloads.
+//!
+//! An artifact is streamed into `.<section>.part` in the output
+//! directory, hashed on the way through, and never held in memory. If
+//! the transfer is cut off, the `.part` file stays behind together with
+//! `.<section>.part.json`, which records the URL and the validator the
+//! server sent for it (a strong `ETag`, or `Last-Modified`). The retry
+//! (or, once retries run out, the next run) asks for the rest with
+//!
+//! ```text
+//! Range: bytes=<bytes on disk>-
+//! If-Range: <validator>
+//! ```
+//!
+//! A `206` whose `Content-Range` starts where the file ends is appended.
+//! Anything else — a `200` because the server ignores ranges or the
+//! artifact changed, a `416`, a different URL — discards the partial
+//! file and starts over. A server that sends no usable validator can't
+//! be resumed safely, so its partial files are not kept.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use log::*;
+use serde::{Deserialize, Serialize};
+use ureq::typestate::WithoutBody;
+use ureq::RequestBuilder;
+
+use crate::checksum::HashingReader;
+use crate::retry::{Attempt, RetryPolicy};
+
+/// A complete download, streamed to a temporary file in the output
+/// directory rather than held in memory. The file is removed when this
+/// is dropped, whether or not it was installed.
+pub(crate) struct Download {
+    pub(crate) path: PathBuf,
+    /// Hex SHA-256, computed while streaming.
+    pub(crate) sha256: String,
+    pub(crate) size: u64,
+}
+
+/// Sidecar of an interrupted download.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct PartMeta {
+    url: String,
+    /// Sent back as `If-Range`, so the server only honours the range
+    /// if the artifact hasn't changed since.
+    validator: String,
+}
+
+impl Download {
+    /// Download `url`, making each attempt with a new `request`.
+    pub(crate) fn fetch(
+        request: impl Fn() -> RequestBuilder<WithoutBody>,
+        policy: &RetryPolicy,
+        section: &str,
+        url: &str,
+        output_dir: &Path,
+    ) -> Result<Download> {
+        // One per section, so parallel sections never share a file.
+        let name: String = section
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let path = output_dir.join(format!(".{}.part", name));
+        let meta_path = output_dir.join(format!(".{}.part.json", name));
+
+        // A retry after an interrupted transfer resumes it.
+        policy.run(section, url, || {
+            fetch_once(&request, policy, section, url, &path, &meta_path)
+        })
+    }
+
+    pub(crate) fn open(&self) -> Result<File> {
+        Ok(File::open(&self.path)?)
+    }
+}
+
+impl Drop for Download {
+    fn drop(&mut self) {
+        // Already gone if it was renamed into place.
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn fetch_once(
+    request: &impl Fn() -> RequestBuilder<WithoutBody>,
+    policy: &RetryPolicy,
+    section: &str,
+    url: &str,
+    path: &Path,
+    meta_path: &Path,
+) -> Result<Attempt<Download>> {
+    let mut resume = partial_download(path, meta_path, url);
+    loop {
+        let mut request = request();
+        if let Some((offset, validator)) = &resume {
+            request = request
+                .header("Range", &format!("bytes={}-", offset))
+                .header("If-Range", validator);
+        }
+        let resp = match request.call() {
+            Ok(resp) => resp,
+            Err(e) => return policy.transport_error(url, e),
+        };
+        let status = resp.status().as_u16();
+        let offset = match (&resume, status) {
+            (Some((offset, _)), 206) if content_range_start(&resp) == Some(*offset) => *offset,
+            (_, 200..=299) if status != 206 => 0,
+            (_, _) if policy.retries_status(status) => {
+                return Ok(Attempt::Retry(format!("Got status {status}")));
+            }
+            (Some(_), _) => {
+                debug!(
+                    "[{}] Can't resume {} (status {}); starting over",
+                    section, url, status
+                );
+                discard(path, meta_path);
+                resume = None;
+                continue;
+            }
+            (None, _) => {
+                return Err(anyhow!(
+                    "[{}] Unexpected status {} downloading {}",
+                    section,
+                    status,
+                    url
+                ))
+            }
+        };
+
+        let mut file = if offset > 0 {
+            info!("[{}] Resuming {} from byte {}", section, url, offset);
+            OpenOptions::new().append(true).open(path)?
+        } else {
+            let validator = strong_validator(&resp);
+            match &validator {
+                Some(validator) => std::fs::write(
+                    meta_path,
+                    serde_json::to_string(&PartMeta {
+                        url: url.to_string(),
+                        validator: validator.clone(),
+                    })?,
+                )?,
+                None => {
+                    let _ = std::fs::remove_file(meta_path);
+                }
+            }
+            File::create(path)?
+        };
+
+        let mut reader = HashingReader::new(resp.into_body().into_reader());
+        if offset > 0 {
+            reader.absorb(File::open(path)?.take(offset))?;
+        }
+        if let Err(e) = std::io::copy(&mut reader, &mut file).and_then(|_| file.flush()) {
+            if !meta_path.exists() {
+                discard(path, meta_path);
+            }
+            return Ok(Attempt::Retry(format!("Download was interrupted: {}", e)));
+        }
+
+        let _ = std::fs::remove_file(meta_path);
+        let (sha256, size) = reader.finish();
+        debug!("[{}] Downloaded {} bytes", section, size);
+        return Ok(Attempt::Done(Download {
+            path: path.to_path_buf(),
+            sha256,
+            size,
+        }));
+    }
+}
+
+/// Length and validator of a resumable partial download of `url`.
+fn partial_download(path: &Path, meta_path: &Path, url: &str) -> Option<(u64, String)> {
+    let meta: PartMeta = serde_json::from_str(&std::fs::read_to_string(meta_path).ok()?).ok()?;
+    let len = std::fs::metadata(path).ok()?.len();
+    (meta.url == url && len > 0).then_some((len, meta.validator))
+}
+
+fn discard(path: &Path, meta_path: &Path) {
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(meta_path);
+}
+
+fn header<'a>(resp: &'a ureq::http::Response<ureq::Body>, name: &str) -> Option<&'a str> {
+    resp.headers().get(name).and_then(|v| v.to_str().ok())
+}
+
+/// `If-Range` only accepts a strong ETag, so a weak one (`W/"..."`)
+/// falls back to `Last-Modified`.
+fn strong_validator(resp: &ureq::http::Response<ureq::Body>) -> Option<String> {
+    header(resp, "ETag")
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(resp, "Last-Modified"))
+        .map(String::from)
+}
+
+/// First byte position of a `Content-Range: bytes <start>-<end>/<len>`.
+fn content_range_start(resp: &ureq::http::Response<ureq::Body>) -> Option<u64> {
+    header(resp, "Content-Range")?
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_agent;
+    use crate::testutil::http_stand_in;
+    use sha2::{Digest, Sha256};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    fn quick() -> RetryPolicy {
+        RetryPolicy {
+            backoff: Duration::ZERO,
+            ..Default::default()
+        }
+    }
+
+    fn artifact() -> Vec<u8> {
+        (0..100_000u32).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn requested_offset(head: &str) -> Option<usize> {
+        head.to_ascii_lowercase().lines().find_map(|l| {
+            l.strip_prefix("range: bytes=")?
+                .strip_suffix('-')?
+                .parse()
+                .ok()
+        })
+    }
+
+    fn response(status: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+        let mut out = format!(
+            "HTTP/1.1 {}\r\nConnection: close\r\n{}\r\n",
+            status, headers
+        )
+        .into_bytes();
+        out.extend_from_slice(body);
+        out
+    }
+
+    #[test]
+    fn interrupted_download_resumes_with_a_range_request() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let data = artifact();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let seen = requests.clone();
+        let body = data.clone();
+        let url = http_stand_in(move |head| {
+            seen.fetch_add(1, Ordering::SeqCst);
+            match requested_offset(head) {
+                Some(offset) if head.contains("\"v1\"") => response(
+                    "206 Partial Content",
+                    &format!(
+                        "Content-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\n",
+                        offset,
+                        body.len() - 1,
+                        body.len(),
+                        body.len() - offset
+                    ),
+                    &body[offset..],
+                ),
+                // Promise everything, deliver 40%, hang up.
+                _ => response(
+                    "200 OK",
+                    &format!("ETag: \"v1\"\r\nContent-Length: {}\r\n", body.len()),
+                    &body[..40_000],
+                ),
+            }
+        }) + "/tool.tar.gz";
+
+        let no_retries = RetryPolicy {
+            max_attempts: 1,
+            ..quick()
+        };
+        assert!(Download::fetch(
+            || http_agent().get(&url),
+            &no_retries,
+            "tool",
+            &url,
+            dir.path()
+        )
+        .is_err());
+        assert_eq!(
+            std::fs::metadata(dir.path().join(".tool.part"))?.len(),
+            40_000
+        );
+
+        let download = Download::fetch(
+            || http_agent().get(&url),
+            &quick(),
+            "tool",
+            &url,
+            dir.path(),
+        )?;
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(std::fs::read(&download.path)?, data);
+        assert_eq!(download.sha256, format!("{:x}", Sha256::digest(&data)));
+        assert_eq!(download.size, data.len() as u64);
+        assert!(!dir.path().join(".tool.part.json").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn retries_resume_within_one_fetch() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let data = artifact();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let seen = requests.clone();
+        let body = data.clone();
+        let url = http_stand_in(move |head| match seen.fetch_add(1, Ordering::SeqCst) {
+            0 => response("503 Service Unavailable", "Content-Length: 0\r\n", b""),
+            1 => response(
+                "200 OK",
+                &format!("ETag: \"v1\"\r\nContent-Length: {}\r\n", body.len()),
+                &body[..40_000],
+            ),
+            _ => {
+                let offset = requested_offset(head).unwrap();
+                response(
+                    "206 Partial Content",
+                    &format!(
+                        "Content-Range: bytes {}-{}/{}\r\n",
+                        offset,
+                        body.len() - 1,
+                        body.len()
+                    ),
+                    &body[offset..],
+                )
+            }
+        }) + "/tool.tar.gz";
+
+        let download = Download::fetch(
+            || http_agent().get(&url),
+            &quick(),
+            "tool",
+            &url,
+            dir.path(),
+        )?;
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        assert_eq!(std::fs::read(&download.path)?, data);
+        assert_eq!(download.sha256, format!("{:x}", Sha256::digest(&data)));
+        Ok(())
+    }
+
+    #[test]
+    fn server_ignoring_the_range_gets_a_full_download() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join(".tool.part"), b"stale bytes")?;
+        let data = artifact();
+        let body = data.clone();
+        let url = http_stand_in(move |_| {
+            response(
+                "200 OK",
+                &format!("ETag: \"v2\"\r\nContent-Length: {}\r\n", body.len()),
+                &body,
+            )
+        }) + "/tool.tar.gz";
+        std::fs::write(
+            dir.path().join(".tool.part.json"),
+            serde_json::to_string(&PartMeta {
+                url: url.clone(),
+                validator: "\"v1\"".to_string(),
+            })?,
+        )?;
+
+        let download = Download::fetch(
+            || http_agent().get(&url),
+            &quick(),
+            "tool",
+            &url,
+            dir.path(),
+        )?;
+        assert_eq!(std::fs::read(&download.path)?, data);
+        assert_eq!(download.sha256, format!("{:x}", Sha256::digest(&data)));
+        Ok(())
+    }
+
+    #[test]
+    fn partial_without_validator_is_not_kept() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let url = http_stand_in(|_| response("200 OK", "Content-Length: 1000\r\n", &[0; 10]))
+            + "/tool.tar.gz";
+        assert!(Download::fetch(
+            || http_agent().get(&url),
+            &quick(),
+            "tool",
+            &url,
+            dir.path()
+        )
+        .is_err());
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn stalled_body_times_out() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        // Sends the headers and part of the body, then goes quiet
+        // without hanging up.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}/tool.tar.gz", listener.local_addr()?);
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 4096]);
+            let _ = stream.write_all(&response("200 OK", "Content-Length: 1000\r\n", &[0; 10]));
+            std::thread::sleep(Duration::from_secs(30));
+        });
+
+        let policy = RetryPolicy {
+            read_timeout: Some(Duration::from_millis(500)),
+            max_attempts: 1,
+            ..quick()
+        };
+        let started = Instant::now();
+        let result = Download::fetch(
+            || policy.apply(http_agent().get(&url)),
+            &policy,
+            "tool",
+            &url,
+            dir.path(),
+        );
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(10));
+        Ok(())
+    }
+
+    #[test]
+    fn completed_download_is_removed_on_drop() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let url = http_stand_in(|_| response("200 OK", "Content-Length: 3\r\n", b"abc")) + "/tool";
+        let download = Download::fetch(
+            || http_agent().get(&url),
+            &quick(),
+            "tool",
+            &url,
+            dir.path(),
+        )?;
+        assert!(download.path.exists());
+        drop(download);
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+}
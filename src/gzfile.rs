@@ -1,6 +1,6 @@
 use crate::Config;
 use anyhow::{anyhow, Result};
-use std::io::{Read, Write};
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 /// A `.gz` file (as opposed to `.tar.gz`) is a single compressed
@@ -10,7 +10,7 @@ use std::path::{Path, PathBuf};
 /// and write the decompressed bytes into `output_dir`.
 pub fn extract_target_from_gzfile(
     section: &str,
-    compressed: &[u8],
+    compressed: impl Read,
     conf: &Config,
     output_dir: &Path,
 ) -> Result<Vec<PathBuf>> {
@@ -27,10 +27,7 @@ pub fn extract_target_from_gzfile(
         .expect("singular_target guarantees rename_to is Some");
     let dest = output_dir.join(dest_name);
 
-    let cbuf = std::io::Cursor::new(compressed);
-    let mut decoder = flate2::read::GzDecoder::new(cbuf);
-    let mut buf = Vec::new();
-    decoder.read_to_end(&mut buf)?;
-    std::fs::File::create(&dest)?.write_all(&buf)?;
+    let mut decoder = flate2::read::GzDecoder::new(compressed);
+    std::io::copy(&mut decoder, &mut std::fs::File::create(&dest)?)?;
     Ok(vec![dest])
 }
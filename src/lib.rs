@@ -1,46 +1,105 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::io::{Read, Write};
 #[cfg(target_family = "unix")]
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
 
 use anyhow::{anyhow, Result};
 use itertools::Itertools;
 use log::*;
 use scraper::{Html, Selector};
 use strfmt::strfmt;
+use ureq::typestate::WithoutBody;
+use ureq::RequestBuilder;
 use url::Url;
 
 pub mod add;
 mod archive;
 mod btlog;
+mod checksum;
+mod configfile;
+pub mod credentials;
+mod download;
 mod gzfile;
+pub mod lockfile;
+pub mod network;
+mod pagecache;
+pub mod platform;
+pub mod ratelimit;
 pub mod reporter;
+pub mod retry;
+mod signature;
 mod tarfile;
 mod tarxzfile;
+pub mod templates;
 #[cfg(test)]
 mod testutil;
+pub mod validators;
+mod version;
 mod zipfile;
 
 use crate::btlog::log_error_with_stack_trace;
-use crate::reporter::{OutputRecord, Reporter};
+use crate::checksum::Verification;
+use crate::credentials::Credentials;
+use crate::download::Download;
+use crate::lockfile::{LockEntry, LockFile};
+use crate::network::NetworkSettings;
+use crate::pagecache::{Page, PageCache};
+use crate::platform::Platform;
+use crate::ratelimit::RateLimiter;
+use crate::reporter::{OutputRecord, Reporter, Status};
+use crate::retry::{Attempt, RetryPolicy};
+use crate::signature::PublicKey;
+use crate::validators::{asset_selection, PageValidators, ValidatorStore};
+use crate::version::VersionScheme;
 
 /// Shared, per-run state passed into every parallel `run_section` call.
-/// `config_write` serializes writes to the INI file (tini has no
-/// concurrency story). `reporter` serializes rows on stdout.
+/// `config_write` serializes the read-modify-write of the INI file
+/// between sections. `reporter` serializes rows on stdout. When
+/// `lock_file` is set, installed versions are recorded there and the
+/// INI file is never written. `validators`, when set, makes page
+/// fetches conditional on the page having changed since the last run.
+/// `rate_limit` tracks each host's request budget across all sections,
+/// and `pages` fetches each distinct page only once per run. Every
+/// request goes through `agent`, with timeouts and retries following
+/// `retry` unless a section overrides them, and carries whatever
+/// `credentials` has for its host. `platform` fills in the `{os}`,
+/// `{arch}` and similar variables.
 pub struct RunContext {
+    pub agent: ureq::Agent,
+    pub credentials: Credentials,
+    pub retry: RetryPolicy,
     pub config_write: std::sync::Mutex<()>,
     pub reporter: Reporter,
+    pub lock_file: Option<LockFile>,
+    pub validators: Option<ValidatorStore>,
+    pub rate_limit: RateLimiter,
+    pub platform: Platform,
+    pages: PageCache,
 }
 
 impl RunContext {
     pub fn new() -> Self {
         RunContext {
+            agent: http_agent(),
+            credentials: Credentials::from_environment(),
+            retry: RetryPolicy::default(),
             config_write: std::sync::Mutex::new(()),
             reporter: Reporter::new(),
+            lock_file: None,
+            validators: None,
+            rate_limit: RateLimiter::default(),
+            platform: Platform::current(),
+            pages: PageCache::default(),
         }
     }
+
+    /// Replace the agent with one using `network`'s proxy and CA
+    /// settings.
+    pub fn set_network(&mut self, network: &NetworkSettings) -> Result<()> {
+        self.agent = network.configure(http_agent_config())?.build().into();
+        Ok(())
+    }
 }
 
 impl Default for RunContext {
@@ -57,13 +116,25 @@ enum Outcome {
     Updated {
         version: String,
         commit: Option<String>,
+        download_url: String,
+        /// Hex SHA-256 and length of the downloaded artifact.
+        sha256: String,
+        size: u64,
     },
+    /// A dry run found a version that would be downloaded.
+    WouldUpdate { version: String },
     /// The remote version was found and is not newer than what's on disk.
     UpToDate { version: String },
     /// The remote scrape produced no match at all.
     NoHit,
     /// A version was found but the download URL's extension isn't one we handle.
     ExtUnsupported { version: String },
+    /// The artifact was downloaded but didn't match its published SHA-256,
+    /// so nothing was extracted.
+    ChecksumMismatch { version: String },
+    /// The artifact's detached signature didn't verify against the
+    /// section's public key, so nothing was extracted.
+    SignatureInvalid { version: String },
 }
 
 impl Outcome {
@@ -74,22 +145,96 @@ impl Outcome {
     fn current_version(&self) -> Option<&str> {
         match self {
             Outcome::Updated { version, .. }
+            | Outcome::WouldUpdate { version }
             | Outcome::UpToDate { version }
-            | Outcome::ExtUnsupported { version } => Some(version.as_str()),
+            | Outcome::ExtUnsupported { version }
+            | Outcome::ChecksumMismatch { version }
+            | Outcome::SignatureInvalid { version } => Some(version.as_str()),
             Outcome::NoHit => None,
         }
     }
+
+    fn status(&self) -> Status {
+        match self {
+            Outcome::Updated { .. } => Status::Updated,
+            Outcome::WouldUpdate { .. } => Status::WouldUpdate,
+            Outcome::UpToDate { .. } => Status::UpToDate,
+            Outcome::NoHit => Status::NoHit,
+            Outcome::ExtUnsupported { .. } => Status::ExtUnsupported,
+            Outcome::ChecksumMismatch { .. } => Status::ChecksumMismatch,
+            Outcome::SignatureInvalid { .. } => Status::SignatureInvalid,
+        }
+    }
 }
 
-/// Build an HTTP agent that surfaces every response (including 4xx/5xx) as
-/// `Ok(Response)` so the retry loops can inspect the body on error statuses
-/// (notably 403 responses from Github, where the body contains the rate-limit
-/// reason).
+/// Sent with every request. Some sites refuse obviously non-browser
+/// user agents.
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/106.0.0.0 Safari/537.36";
+
+/// Build the HTTP agent for a run. It is created once and shared, so
+/// connections (and their TLS sessions) to the same host are kept alive
+/// and reused across sections and retries. It surfaces every response
+/// (including 4xx/5xx) as `Ok(Response)` so the retry loops can inspect
+/// the body on error statuses (notably 403 responses from Github, where
+/// the body contains the rate-limit reason).
 fn http_agent() -> ureq::Agent {
+    http_agent_config().build().into()
+}
+
+fn http_agent_config() -> ureq::config::ConfigBuilder<ureq::typestate::AgentScope> {
     ureq::Agent::config_builder()
         .http_status_as_error(false)
-        .build()
-        .into()
+        .user_agent(USER_AGENT)
+        // Room for a few workers talking to api.github.com at once.
+        .max_idle_connections_per_host(8)
+        // Credentials are for the host they were looked up for, never
+        // for wherever it redirects to.
+        .redirect_auth_headers(ureq::config::RedirectAuthHeaders::SameHost)
+}
+
+/// A request for `url`, one of the assets listed with `hit`, with
+/// `policy`'s timeouts and the host's credentials. An asset with an API
+/// URL is fetched from there instead, which only serves the asset
+/// itself to a request that accepts `application/octet-stream`.
+fn asset_request(
+    ctx: &RunContext,
+    policy: &RetryPolicy,
+    hit: &Hit,
+    url: &str,
+) -> RequestBuilder<WithoutBody> {
+    let Some(api_url) = hit.api_urls.get(url) else {
+        return ctx.credentials.apply(url, policy.apply(ctx.agent.get(url)));
+    };
+    ctx.credentials
+        .apply(api_url, policy.apply(ctx.agent.get(api_url)))
+        .header("Accept", "application/octet-stream")
+}
+
+/// Fetch a small text file that accompanies a download, such as a
+/// checksum list or a detached signature.
+fn fetch_text(
+    policy: &RetryPolicy,
+    section: &str,
+    url: &str,
+    request: impl Fn() -> RequestBuilder<WithoutBody>,
+) -> Result<String> {
+    policy.run(section, url, || {
+        let resp = match request().call() {
+            Ok(resp) => resp,
+            Err(e) => return policy.transport_error(url, e),
+        };
+        let status = resp.status().as_u16();
+        match status {
+            200..=299 => match resp.into_body().read_to_string() {
+                Ok(text) => Ok(Attempt::Done(text)),
+                Err(e) => policy.transport_error(url, e),
+            },
+            _ if policy.retries_status(status) => {
+                Ok(Attempt::Retry(format!("Got status {status}")))
+            }
+            _ => Err(anyhow!("Unexpected status {} fetching {}", status, url)),
+        }
+    })
 }
 
 /// One file the user wants out of an archive.
@@ -98,6 +243,7 @@ fn http_agent() -> ureq::Agent {
 /// `rename_to` is the destination filename on disk:
 ///   - `Some(name)` — write the matched entry to `name` (singular mode).
 ///   - `None` — write it under its original archive basename (plural mode).
+///
 /// `pattern_str` is the raw, uncompiled pattern, kept around for
 /// "does this file already exist on disk?" checks where we treat the
 /// pattern as a literal filename (the realistic case for plural mode).
@@ -125,6 +271,8 @@ struct Config {
     method: String,
     template: String,
     version: Option<String>,
+    /// How `version` is ordered against what the remote reports.
+    version_scheme: VersionScheme,
 
     /// More direct strategy
     /// The HTTP page link that contains the download link
@@ -136,14 +284,48 @@ struct Config {
     /// This will be matched
     anchor_text: String,
     /// The version tag to check. The "text" of the tag will be used.
+    /// Without one, the version is the `version` group of `anchor_text`.
     version_tag: Option<String>,
     /// The commit tag is used if the "version" always comes
     /// back as the same thing. An example of this is neovim,
     /// where the project keeps using the tag `stable`, but the
     /// commit hash changes. In this case, we'll use the commit
-    /// as a disambiguator.
+    /// as a disambiguator. On HTML pages this is a CSS selector,
+    /// and the commit is the text of the tag, or the value of its
+    /// `commit_attribute` if that is set.
     commit_tag: Option<String>,
+    commit_attribute: Option<String>,
     commit: Option<String>,
+    /// Where to find the SHA-256 of the download, given directly.
+    checksum_url: Option<String>,
+    /// Regex matched against the same links as `anchor_text` to find
+    /// the checksum file. Either of these makes verification mandatory;
+    /// without them, `api_json` sections use whatever checksum asset
+    /// `checksum::discover` finds, if any.
+    checksum_anchor_text: Option<String>,
+    /// JSONPath selecting one `sha256:<hex>` digest per asset, parallel
+    /// to `anchor_tag` (GitHub's `$.assets.*.digest`).
+    digest_tag: Option<String>,
+    /// JSONPath selecting, parallel to `anchor_tag`, the API URL each
+    /// asset is actually fetched from (GitHub's `$.assets.*.url`). The
+    /// only way to download from a private repository.
+    asset_url_tag: Option<String>,
+    /// Key the artifact's detached signature must verify against, from
+    /// `minisign_pubkey` or `ssh_pubkey`. When set, an artifact without
+    /// a valid signature is never installed.
+    signature_key: Option<PublicKey>,
+    /// Where to find the signature, given directly.
+    signature_url: Option<String>,
+    /// Regex matched against the same links as `anchor_text` to find
+    /// the signature. Without this (or `signature_url`), `api_json`
+    /// sections look for the download URL plus `.minisig` / `.sig`.
+    signature_anchor_text: Option<String>,
+    /// Going in to `process`, the validators from the last fetch of
+    /// `page_url`, to make this fetch conditional. Coming out, those of
+    /// this fetch, for the caller to keep if the run went well.
+    validators: Option<PageValidators>,
+    /// Timeouts and retries for this section's requests.
+    retry: RetryPolicy,
     /// One or more files to pull out of the downloaded archive.
     /// Singular mode produces exactly one entry (with `rename_to` set);
     /// plural mode produces N entries with `rename_to == None`.
@@ -157,6 +339,19 @@ impl Config {
         }
     }
 
+    /// See `validators::asset_selection`.
+    fn asset_selection(&self) -> String {
+        let targets = self
+            .extraction_targets
+            .iter()
+            .flat_map(|t| [t.pattern_str.as_str(), t.rename_to.as_deref().unwrap_or("")]);
+        asset_selection(
+            [self.anchor_tag.as_str(), self.anchor_text.as_str()]
+                .into_iter()
+                .chain(targets),
+        )
+    }
+
     /// The single extraction target if this config was built in
     /// singular mode (one target with an explicit `rename_to`). Used to
     /// gate non-archive downloads (.exe / .gz / .com / .AppImage),
@@ -169,11 +364,25 @@ impl Config {
     }
 }
 
+/// What fetching and scraping `page_url` produced.
+enum Fetched {
+    /// The server answered a conditional request with `304`.
+    NotModified,
+    Page(Option<Hit>),
+}
+
 #[derive(Debug, PartialEq)]
 struct Hit {
     version: String,
     commit: Option<String>,
     download_url: String,
+    checksum_url: Option<String>,
+    /// Hex SHA-256 of the artifact, when the metadata reports one.
+    digest: Option<String>,
+    signature_url: Option<String>,
+    /// The API URL of each asset on the page, by its download URL, when
+    /// `asset_url_tag` is set.
+    api_urls: HashMap<String, String>,
 }
 
 /// Read a section of the config file (ini file) into a hashmap.
@@ -187,19 +396,20 @@ fn read_section_into_map(conf: &tini::Ini, section: &str) -> HashMap<String, Str
     tmp
 }
 
-type Templates = HashMap<String, HashMap<String, String>>;
+/// Templates by name, each a map of its fields.
+pub type Templates = HashMap<String, HashMap<String, String>>;
 
-/// Mutate the config to replace a template with the template values.
+/// The values of a section, with the fields of its templates filled in.
 ///
 /// If `template` is specified in a section, we must use it! Look up
 /// the fields that are defined in a template with that name, and
-/// insert those fields into the `Config` object that represents
-/// that section.
+/// copy every one of them into the section's values, unless the section
+/// sets that field itself.
 ///
-/// Beyond simple substitution, the individual template values can
-/// also themselves be used as templates using the handlebars
-/// format. Any fields defined in the *section* can be substituted
-/// into each template item if the `{name}` of that item is used.
+/// Beyond simple substitution, the individual values can also
+/// themselves be used as templates using the handlebars format. Any
+/// field of the section (or of its templates) can be substituted into
+/// another if the `{name}` of that field is used.
 ///
 /// Let's look at a real example. Here is a template for a "github"
 /// releases page:
@@ -222,61 +432,158 @@ type Templates = HashMap<String, HashMap<String, String>>;
 /// version = 13.0.0
 /// ```
 ///
-/// This function does the following:
+/// This function adds the fields in the template (page_url, anchor_tag,
+/// version_tag) to the ripgrep values. When the fields are read, the
+/// `{project}` in `page_url` is replaced by the `project` of the
+/// ripgrep section.
 ///
-/// 1. Add the fields in the template (page_url, anchor_tag, version_tag)
-///    into the ripgrep config hashmap
-/// 2. Sees that the `{project}` template variable appears in the `page_url`
-///    field, and substitutes the value of the actual `project` value
-///    in the ripgrep section.
-fn insert_fields_from_template(
-    cf: &mut Config,
+/// A template can itself name a `template` to build on, and a section or
+/// template can name several, as in `template = github_api_latest, musl`.
+/// They're applied in order, parents before the template that names
+/// them, so each overrides the ones before it.
+fn resolve_templates(
+    section: &str,
     templates: &Templates,
     values: &HashMap<String, String>,
+) -> Result<HashMap<String, String>> {
+    let mut resolved = HashMap::new();
+    if let Some(names) = values.get("template") {
+        debug!("Config has a template: {:?}", values);
+        apply_templates(section, templates, names, &mut Vec::new(), &mut resolved)?;
+    }
+    resolved.extend(values.iter().map(|(k, v)| (k.clone(), v.clone())));
+    debug!("Substitutions complete: {:?}", &resolved);
+    Ok(resolved)
+}
+
+/// Add the fields of each of the comma-separated templates in `names`,
+/// and of their parents, to `resolved`. `chain` holds the templates
+/// being applied, to catch one that (indirectly) names itself.
+fn apply_templates(
+    section: &str,
+    templates: &Templates,
+    names: &str,
+    chain: &mut Vec<String>,
+    resolved: &mut HashMap<String, String>,
 ) -> Result<()> {
-    if let Some(t) = values.get("template") {
-        debug!("Config has a template: {:?}", &values);
-        cf.template = t.clone();
-        let template_fields = templates.get(&cf.template).ok_or_else(|| {
+    for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        if chain.iter().any(|t| t == name) {
+            return Err(anyhow!(
+                "[{}] Template {} builds on itself: {} -> {}",
+                section,
+                name,
+                chain.join(" -> "),
+                name
+            ));
+        }
+        let fields = templates.get(name).ok_or_else(|| {
             anyhow!(
                 "The specified template '{}' was not found in the \
                     list of available templates: {:?}",
-                &t,
+                name,
                 templates.keys().collect::<Vec<_>>()
             )
         })?;
+        if let Some(parents) = fields.get("template") {
+            chain.push(name.to_string());
+            apply_templates(section, templates, parents, chain, resolved)?;
+            chain.pop();
+        }
+        resolved.extend(fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+    Ok(())
+}
 
-        if let Some(value) = template_fields.get("page_url") {
-            cf.page_url = strfmt(value, values)?;
-        };
-        if let Some(value) = template_fields.get("anchor_tag") {
-            cf.anchor_tag = strfmt(value, values)?;
-        };
-        if let Some(value) = template_fields.get("version_tag") {
-            cf.version_tag = Some(strfmt(value, values)?);
-        };
-        if let Some(value) = template_fields.get("commit_tag") {
-            cf.commit_tag = Some(strfmt(value, values)?);
-        };
-        if let Some(value) = template_fields.get("method") {
-            cf.method = strfmt(value, values)?;
-        };
+/// One run of `run_section`: a section of the config, or one of the
+/// `platforms` a section lists. Each platform is reported, recorded in
+/// the lock file and validator store and versioned in the config on
+/// its own, under the name `<section>@<platform>`. Platforms asking for
+/// their page alike still share one fetch of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionRun {
+    /// What the run is reported and recorded as.
+    pub name: String,
+    /// The config section it reads.
+    pub section: String,
+    /// The platform to fetch for, if not the one lifter runs on.
+    pub platform: Option<Platform>,
+}
+
+impl SectionRun {
+    /// The runs for `section`: one per platform if it lists `platforms`,
+    /// otherwise just the section itself.
+    pub fn expand(section: &str, templates: &Templates, conf: &tini::Ini) -> Vec<SectionRun> {
+        let platforms =
+            resolve_templates(section, templates, &read_section_into_map(conf, section))
+                .and_then(|values| read_platforms(section, &values));
+        match platforms {
+            Ok(platforms) if !platforms.is_empty() => platforms
+                .into_iter()
+                .map(|platform| SectionRun {
+                    name: platform.qualify(section),
+                    section: section.to_string(),
+                    platform: Some(platform),
+                })
+                .collect(),
+            // A bad section is left for `run_section` to report.
+            _ => vec![SectionRun {
+                name: section.to_string(),
+                section: section.to_string(),
+                platform: None,
+            }],
+        }
+    }
+}
+
+/// The `platforms` listed in a section's `values`.
+fn read_platforms(section: &str, values: &HashMap<String, String>) -> Result<Vec<Platform>> {
+    let Some(raw) = values.get("platforms") else {
+        return Ok(vec![]);
     };
+    let platforms = raw
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| p.parse().map_err(|e| anyhow!("[{}] {}", section, e)))
+        .collect::<Result<Vec<Platform>>>()?;
+    Ok(platforms.into_iter().unique().collect())
+}
 
-    debug!("Substitutions complete: {:?}", &cf);
-    Ok(())
+/// Turn a section's `values` into those of one of its platforms: the
+/// `version` and `commit` are the ones recorded for that platform, and
+/// unless the section says otherwise, `<section><exe_suffix>` is saved
+/// as `<section>-<platform><exe_suffix>` so platforms don't overwrite
+/// each other's files.
+fn values_for_platform(values: &mut HashMap<String, String>, section: &str, platform: &Platform) {
+    for key in ["version", "commit"] {
+        match values.remove(&platform.qualify(key)) {
+            Some(value) => values.insert(key.to_string(), value),
+            None => values.remove(key),
+        };
+    }
+    if !values.contains_key("target_filenames_to_extract_from_archive") {
+        let suffix = platform.exe_suffix();
+        values
+            .entry("target_filename_to_extract_from_archive".to_string())
+            .or_insert_with(|| format!("{section}{suffix}"));
+        values
+            .entry("desired_filename".to_string())
+            .or_insert_with(|| format!("{section}-{platform}{suffix}"));
+    }
 }
 
 /// All the read-only inputs `run_section_inner` needs. Bundled into
 /// a struct so the inner signature stays manageable as the pipeline
-/// grows (this is also where a future `dry_run` or
-/// `concurrency_limit` flag would slot in).
+/// grows (this is also where a future `concurrency_limit` flag would
+/// slot in).
 struct SectionInputs<'a> {
-    section: &'a str,
+    run: &'a SectionRun,
     templates: &'a Templates,
     conf: &'a tini::Ini,
     filename: &'a str,
     output_dir: &'a Path,
+    /// Resolve and compare versions, but download and write nothing.
+    dry_run: bool,
     ctx: &'a RunContext,
 }
 
@@ -285,19 +592,21 @@ struct SectionInputs<'a> {
 /// trace). Returns `Ok(())` unconditionally so one failing section
 /// doesn't poison the `rayon` iterator.
 pub fn run_section(
-    section: &str,
+    run: &SectionRun,
     templates: &Templates,
     conf: &tini::Ini,
     filename: &str,
     output_dir: &Path,
+    dry_run: bool,
     ctx: &RunContext,
 ) {
     let inputs = SectionInputs {
-        section,
+        run,
         templates,
         conf,
         filename,
         output_dir,
+        dry_run,
         ctx,
     };
 
@@ -308,23 +617,25 @@ pub fn run_section(
 
     let result = run_section_inner(&inputs, &mut previous_version, &mut file_name);
 
-    let (updated, current_version) = match &result {
+    let (updated, current_version, status) = match &result {
         Ok(outcome) => (
             outcome.updated(),
             outcome.current_version().map(String::from),
+            outcome.status(),
         ),
         Err(e) => {
             log_error_with_stack_trace(format!("{}", e));
-            (false, None)
+            (false, None, Status::Error)
         }
     };
 
     ctx.reporter.emit(&OutputRecord {
         updated,
-        tool_name: section.to_string(),
+        tool_name: run.name.clone(),
         file_name,
         previous_version,
         current_version,
+        status,
     });
 }
 
@@ -334,30 +645,53 @@ fn run_section_inner(
     file_name: &mut Option<String>,
 ) -> Result<Outcome> {
     let SectionInputs {
-        section,
+        run,
         templates,
         conf,
         filename,
         output_dir,
+        dry_run,
         ctx,
     } = *inputs;
 
-    let tmp = read_section_into_map(conf, section);
+    let section = run.name.as_str();
+    let values = read_section_into_map(conf, &run.section);
+    let mut tmp = resolve_templates(section, templates, &values)?;
+    let platform = match &run.platform {
+        Some(platform) => {
+            values_for_platform(&mut tmp, &run.section, platform);
+            platform
+        }
+        None if !read_platforms(section, &tmp)?.is_empty() => {
+            return Err(anyhow!(
+                "[{}] Sections with platforms run once per platform, \
+                 from SectionRun::expand",
+                section
+            ));
+        }
+        None => &ctx.platform,
+    };
+    for (name, value) in platform.variables(section, &tmp)? {
+        tmp.entry(name).or_insert(value);
+    }
     let mut cf = Config::new();
-    insert_fields_from_template(&mut cf, templates, &tmp)?;
+    if let Some(value) = tmp.get("template") {
+        cf.template = value.clone();
+    };
+    if let Some(value) = tmp.get("method") {
+        cf.method = strfmt(value, &tmp)?;
+    };
 
     // First get the project - required
     match tmp.get("page_url") {
-        Some(p) => cf.page_url = p.clone(),
+        Some(p) => cf.page_url = strfmt(p, &tmp)?,
         None => {
-            if cf.page_url.is_empty() {
-                warn!(
-                    "[{}] Section {} is missing required field \
-                     \"page_url\"",
-                    section, section
-                );
-                return Ok(Outcome::NoHit);
-            }
+            warn!(
+                "[{}] Section {} is missing required field \
+                 \"page_url\"",
+                section, section
+            );
+            return Ok(Outcome::NoHit);
         }
     };
     debug!("[{}] Processing: {}", section, &cf.page_url);
@@ -373,12 +707,49 @@ fn run_section_inner(
     if let Some(value) = tmp.get("version_tag") {
         cf.version_tag = Some(strfmt(value, &tmp)?);
     };
+    if cf.version_tag.is_none() && !has_version_group(&cf.anchor_text)? {
+        return Err(anyhow!(
+            "[{}] Needs a version_tag, or a (?P<version>...) group in anchor_text",
+            section
+        ));
+    }
 
     if let Some(value) = tmp.get("commit_tag") {
         cf.commit_tag = Some(strfmt(value, &tmp)?);
     };
 
+    if let Some(value) = tmp.get("commit_attribute") {
+        cf.commit_attribute = Some(strfmt(value, &tmp)?);
+    };
+
+    if let Some(value) = tmp.get("digest_tag") {
+        cf.digest_tag = Some(strfmt(value, &tmp)?);
+    };
+
+    if let Some(value) = tmp.get("asset_url_tag") {
+        cf.asset_url_tag = Some(strfmt(value, &tmp)?);
+    };
+
+    if let Some(value) = tmp.get("checksum_url") {
+        cf.checksum_url = Some(strfmt(value, &tmp)?);
+    };
+
+    if let Some(value) = tmp.get("checksum_anchor_text") {
+        cf.checksum_anchor_text = Some(strfmt(value, &tmp)?);
+    };
+
+    cf.signature_key = read_signature_key(section, &tmp)?;
+
+    if let Some(value) = tmp.get("signature_url") {
+        cf.signature_url = Some(strfmt(value, &tmp)?);
+    };
+
+    if let Some(value) = tmp.get("signature_anchor_text") {
+        cf.signature_anchor_text = Some(strfmt(value, &tmp)?);
+    };
+
     cf.extraction_targets = build_extraction_targets(section, &tmp)?;
+    cf.retry = ctx.retry.with_overrides(section, &tmp)?;
 
     if let Some(value) = tmp.get("version") {
         cf.version = Some(strfmt(value, &tmp)?);
@@ -386,6 +757,14 @@ fn run_section_inner(
     if let Some(value) = tmp.get("commit") {
         cf.commit = Some(strfmt(value, &tmp)?);
     };
+    if let Some(value) = tmp.get("version_scheme") {
+        cf.version_scheme = value.parse().map_err(|e| anyhow!("[{}] {}", section, e))?;
+    };
+    // The lock file, if there is one, knows better than the config.
+    if let Some(entry) = ctx.lock_file.as_ref().and_then(|l| l.get(section)) {
+        cf.version = Some(entry.version);
+        cf.commit = entry.commit;
+    }
 
     // Publish the two fields needed for the CSV row now that
     // substitutions are done. They remain accurate even on error paths
@@ -393,31 +772,79 @@ fn run_section_inner(
     *previous_version = cf.version.clone();
     *file_name = file_name_for_report(&cf.extraction_targets);
 
-    let outcome = process(section, &mut cf, output_dir)?;
+    if let Some(store) = &ctx.validators {
+        if let Some(version) = &cf.version {
+            if target_file_already_exists(&cf, output_dir) {
+                cf.validators = store.get(section, &cf.page_url, version, &cf.asset_selection());
+            }
+        }
+    }
+
+    let outcome = process(section, &mut cf, output_dir, dry_run, ctx)?;
 
-    if let Outcome::Updated { version, commit } = &outcome {
-        let _lock = ctx.config_write.lock().unwrap();
-        let conf_write = tini::Ini::from_file(filename).unwrap();
+    // Only remember this fetch if it ended with the latest version
+    // installed; otherwise a `304` next run would hide the problem.
+    let installed = match &outcome {
+        Outcome::Updated { version, .. } => Some(version),
+        Outcome::UpToDate { .. } => cf.version.as_ref(),
+        _ => None,
+    };
+    if let (Some(store), Some(version), Some(validators), false) =
+        (&ctx.validators, installed, cf.validators.take(), dry_run)
+    {
+        let selection = cf.asset_selection();
+        if let Err(e) = store.record(section, &cf.page_url, version, &selection, validators) {
+            warn!("[{}] {}", section, e);
+        }
+    }
+
+    if let Outcome::Updated {
+        version,
+        commit,
+        download_url,
+        sha256,
+        size,
+    } = &outcome
+    {
         if let Some(new_commit) = commit {
             info!(
                 "[{}] Downloaded new version: {}:{}",
                 section, &version, new_commit
             );
-            conf_write
-                .section(section)
-                .item("version", version)
-                .item("commit", new_commit)
-                .to_file(filename)
-                .unwrap();
         } else {
             info!("[{}] Downloaded new version: {}", section, &version);
-            conf_write
-                .section(section)
-                .item("version", version)
-                .to_file(filename)
-                .unwrap();
         }
-        debug!("[{}] Updated config file.", section);
+        if let Some(lock_file) = &ctx.lock_file {
+            lock_file.record(
+                section,
+                LockEntry {
+                    version: version.clone(),
+                    commit: commit.clone(),
+                    download_url: Some(download_url.clone()),
+                    sha256: Some(sha256.clone()),
+                    size: Some(*size),
+                    updated_at: Some(reporter::now_rfc3339_utc()),
+                },
+            )?;
+            debug!("[{}] Updated lock file.", section);
+        } else {
+            let key = |name: &str| match &run.platform {
+                Some(platform) => platform.qualify(name),
+                None => name.to_string(),
+            };
+            let (version_key, commit_key) = (key("version"), key("commit"));
+            let mut items = vec![(version_key.as_str(), version.as_str())];
+            if let Some(new_commit) = commit {
+                items.push((commit_key.as_str(), new_commit.as_str()));
+            }
+            // The mutex guards no data, so a poisoned one is still usable.
+            let _lock = ctx
+                .config_write
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            configfile::update_section(Path::new(filename), &run.section, &items)?;
+            debug!("[{}] Updated config file.", section);
+        }
     }
     Ok(outcome)
 }
@@ -511,6 +938,30 @@ fn build_extraction_targets(
     }
 }
 
+/// Parse the section's signing key. `minisign_pubkey` and `ssh_pubkey`
+/// are mutually exclusive; `signature_namespace` (default `file`, as
+/// with `ssh-keygen -Y sign -n file`) only applies to the latter.
+fn read_signature_key(section: &str, tmp: &HashMap<String, String>) -> Result<Option<PublicKey>> {
+    let key = match (tmp.get("minisign_pubkey"), tmp.get("ssh_pubkey")) {
+        (Some(_), Some(_)) => {
+            return Err(anyhow!(
+                "[{}] minisign_pubkey and ssh_pubkey are mutually exclusive",
+                section
+            ))
+        }
+        (Some(value), None) => PublicKey::minisign(value),
+        (None, Some(value)) => {
+            let namespace = tmp
+                .get("signature_namespace")
+                .map(String::as_str)
+                .unwrap_or("file");
+            PublicKey::ssh(value, namespace)
+        }
+        (None, None) => return Ok(None),
+    };
+    key.map(Some).map_err(|e| anyhow!("[{}] {}", section, e))
+}
+
 /// Build the `file_name` field for the CSV reporter. Multiple targets
 /// are joined with `;` so the row stays informative even in plural mode.
 fn file_name_for_report(targets: &[ExtractionTarget]) -> Option<String> {
@@ -540,30 +991,50 @@ fn target_file_already_exists(conf: &Config, output_dir: &Path) -> bool {
             .all(|t| output_dir.join(t.predicted_output_name()).exists())
 }
 
-fn process(section: &str, conf: &mut Config, output_dir: &Path) -> Result<Outcome> {
-    let url = &conf.page_url;
+fn process(
+    section: &str,
+    conf: &mut Config,
+    output_dir: &Path,
+    dry_run: bool,
+    ctx: &RunContext,
+) -> Result<Outcome> {
+    let url = conf.page_url.clone();
 
-    let parse_result = match conf.method.as_str() {
-        "api_json" => parse_json(section, conf, url)?,
-        _ => parse_html_page(section, conf, url)?,
+    let fetched = match conf.method.as_str() {
+        "api_json" => parse_json(section, conf, &url, ctx)?,
+        _ => parse_html_page(section, conf, &url, ctx)?,
     };
 
-    let hit = match parse_result {
-        Some(hit) => hit,
-        None => return Ok(Outcome::NoHit),
+    let hit = match fetched {
+        Fetched::NotModified => {
+            // Only asked when the recorded version is installed.
+            info!("[{}] {} is unchanged since the last run", section, url);
+            return Ok(Outcome::UpToDate {
+                version: conf.version.clone().unwrap_or_default(),
+            });
+        }
+        Fetched::Page(Some(hit)) => hit,
+        Fetched::Page(None) => return Ok(Outcome::NoHit),
     };
 
-    let existing_version = conf.version.as_ref().unwrap();
-    // TODO: must compare each of the components of the version string as integers.
-    if target_file_already_exists(conf, output_dir) && &hit.version < existing_version {
-        debug!(
-            "[{}] Found version is not newer: {}; Skipping.",
-            section, &hit.version
+    // A section with no recorded version (a new section when installed
+    // versions live in the lock file) has nothing to compare against.
+    let existing_version = conf.version.as_deref().unwrap_or_default();
+    // `None` means the versions differ but can't be ordered (opaque
+    // scheme, or a tag with no digits); that falls through to download.
+    let ordering = conf
+        .version
+        .as_deref()
+        .and_then(|v| conf.version_scheme.compare(&hit.version, v));
+    if target_file_already_exists(conf, output_dir) && ordering == Some(Ordering::Less) {
+        info!(
+            "[{}] Found version {} is older than existing version {}; not downgrading.",
+            section, &hit.version, existing_version
         );
         return Ok(Outcome::UpToDate {
             version: hit.version,
         });
-    } else if target_file_already_exists(conf, output_dir) && &hit.version == existing_version {
+    } else if target_file_already_exists(conf, output_dir) && ordering == Some(Ordering::Equal) {
         // If a commit tag has been specified for this conf, we should check
         // that too. If the version tag is the same, and the commit hash
         // is merely different, we will also consider that as a new version.
@@ -597,33 +1068,38 @@ fn process(section: &str, conf: &mut Config, output_dir: &Path) -> Result<Outcom
         };
     }
 
-    info!("[{}] Downloading version {}", section, &hit.version);
-
     let download_url = &hit.download_url;
+    // SourceForge links to a file's download page, `<file>/download`.
+    let file_url = match Url::parse(download_url) {
+        Ok(u) if u.host_str().is_some_and(|h| h.ends_with("sourceforge.net")) => download_url
+            .strip_suffix("/download")
+            .unwrap_or(download_url),
+        _ => download_url,
+    };
     let ext = {
-        if vec![".tar.gz", ".tgz"]
+        if [".tar.gz", ".tgz"]
             .iter()
-            .any(|ext| download_url.ends_with(ext))
+            .any(|ext| file_url.ends_with(ext))
         {
             ".tar.gz"
-        } else if download_url.ends_with(".gz") {
+        } else if file_url.ends_with(".gz") {
             ".gz"
-        } else if vec![".tar.xz", ".txz"]
+        } else if [".tar.xz", ".txz"]
             .iter()
-            .any(|ext| download_url.ends_with(ext))
+            .any(|ext| file_url.ends_with(ext))
         {
             ".tar.xz"
-        } else if download_url.ends_with(".zip") {
+        } else if file_url.ends_with(".zip") {
             ".zip"
-        } else if download_url.ends_with(".exe") {
+        } else if file_url.ends_with(".exe") {
             ".exe"
-        } else if download_url.ends_with(".com") {
+        } else if file_url.ends_with(".com") {
             ".com"
-        } else if download_url.ends_with(".appimage") {
+        } else if file_url.ends_with(".appimage") {
             ".appimage"
-        } else if download_url.ends_with(".AppImage") {
+        } else if file_url.ends_with(".AppImage") {
             ".AppImage"
-        } else if let Some(suffix) = slice_from_end(download_url, 8) {
+        } else if let Some(suffix) = slice_from_end(file_url, 8) {
             // Look at the last 8 chars of the url -> if there's no dot, that
             // probably means no file extension is present, which likely means that
             // the download is a binary.
@@ -654,22 +1130,63 @@ fn process(section: &str, conf: &mut Config, output_dir: &Path) -> Result<Outcom
         }
     };
 
-    let resp = http_agent().get(download_url)
-            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/106.0.0.0 Safari/537.36")
-            .call()?;
-    let mut reader = resp.into_body().into_reader();
-    let mut buf: Vec<u8> = Vec::new();
-    reader.read_to_end(&mut buf)?;
+    if dry_run {
+        info!(
+            "[{}] Would download version {} from {}",
+            section, &hit.version, download_url
+        );
+        return Ok(Outcome::WouldUpdate {
+            version: hit.version,
+        });
+    }
+    info!("[{}] Downloading version {}", section, &hit.version);
+
+    let download = Download::fetch(
+        || asset_request(ctx, &conf.retry, &hit, download_url),
+        &conf.retry,
+        section,
+        download_url,
+        output_dir,
+    )?;
+
+    // Verify before any extractor touches the bytes.
+    if !verify_download(ctx, section, conf, &hit, &download.sha256)? {
+        return Ok(Outcome::ChecksumMismatch {
+            version: hit.version,
+        });
+    }
+    if let Some(key) = &conf.signature_key {
+        let signature_url = hit.signature_url.as_deref().ok_or_else(|| {
+            anyhow!(
+                "[{}] a signing key is configured but no signature was found for {}",
+                section,
+                download_url
+            )
+        })?;
+        let signature = fetch_text(&conf.retry, section, signature_url, || {
+            asset_request(ctx, &conf.retry, &hit, signature_url)
+        })?;
+        if let Err(e) = key.verify(download.open()?, &signature) {
+            error!(
+                "[{}] Signature {} does not verify: {}. Not installing.",
+                section, signature_url, e
+            );
+            return Ok(Outcome::SignatureInvalid {
+                version: hit.version,
+            });
+        }
+        info!("[{}] Signature verified: {}", section, signature_url);
+    }
 
     let extracted: Vec<PathBuf> = if ext == ".tar.xz" {
-        tarxzfile::extract_target_from_tarxz(&mut buf, conf, output_dir)
+        tarxzfile::extract_target_from_tarxz(download.open()?, conf, output_dir)
     } else if ext == ".zip" {
-        zipfile::extract_target_from_zipfile(&mut buf, conf, output_dir)?
+        zipfile::extract_target_from_zipfile(download.open()?, conf, output_dir)?
     } else if ext == ".tar.gz" {
-        tarfile::extract_target_from_tarfile(&mut buf, conf, output_dir)
+        tarfile::extract_target_from_tarfile(download.open()?, conf, output_dir)
     } else if ext == ".gz" {
-        gzfile::extract_target_from_gzfile(section, &buf, conf, output_dir)?
-    } else if vec![".exe", "", ".com", ".appimage", ".AppImage"].contains(&ext) {
+        gzfile::extract_target_from_gzfile(section, download.open()?, conf, output_dir)?
+    } else if [".exe", "", ".com", ".appimage", ".AppImage"].contains(&ext) {
         // Single-file downloads (Windows executables, AppImages,
         // bare binaries) aren't archives — there's nothing to match
         // against. Only the singular form is meaningful here.
@@ -683,17 +1200,14 @@ fn process(section: &str, conf: &mut Config, output_dir: &Path) -> Result<Outcom
         })?;
         let desired_filename = target.rename_to.as_ref().unwrap();
         let dest = output_dir.join(desired_filename);
-        let tmp_dest = output_dir.join(format!("{}.tmp", desired_filename));
-
-        let mut output = std::fs::File::create(&tmp_dest)?;
         info!(
             "[{}] Saving {} to {}",
             section,
             &download_url,
             dest.display()
         );
-        output.write_all(&buf)?;
-        std::fs::rename(&tmp_dest, &dest)?;
+        // The download already sits in `output_dir`, so this is atomic.
+        std::fs::rename(&download.path, &dest)?;
         vec![dest]
     } else {
         Vec::new()
@@ -708,9 +1222,83 @@ fn process(section: &str, conf: &mut Config, output_dir: &Path) -> Result<Outcom
     Ok(Outcome::Updated {
         version: hit.version,
         commit: hit.commit,
+        sha256: download.sha256.clone(),
+        size: download.size,
+        download_url: hit.download_url,
     })
 }
 
+/// Check the download's hex SHA-256, `actual`, against every digest
+/// known for `hit`: the per-asset `digest` from an `api_json` response,
+/// and the entry in its checksum file. Returns `Ok(false)` on a
+/// mismatch. Errors when a checksum the section explicitly asked for
+/// can't be checked at all.
+fn verify_download(
+    ctx: &RunContext,
+    section: &str,
+    conf: &Config,
+    hit: &Hit,
+    actual: &str,
+) -> Result<bool> {
+    if let Some(expected) = &hit.digest {
+        if actual != expected {
+            error!(
+                "[{}] SHA-256 mismatch for {}: asset digest is {}, got {}. Not installing.",
+                section, &hit.download_url, expected, actual
+            );
+            return Ok(false);
+        }
+        info!("[{}] SHA-256 verified against asset digest", section);
+    }
+
+    let checksum_required = conf.checksum_url.is_some() || conf.checksum_anchor_text.is_some();
+    if hit.digest.is_some() && !checksum_required {
+        // An auto-discovered checksum file would only repeat the digest.
+        return Ok(true);
+    }
+    if let Some(checksum_url) = &hit.checksum_url {
+        let checksums = fetch_text(&conf.retry, section, checksum_url, || {
+            asset_request(ctx, &conf.retry, hit, checksum_url)
+        })?;
+        match checksum::verify(actual, &hit.download_url, &checksums) {
+            Verification::Verified => {
+                info!("[{}] SHA-256 verified against {}", section, checksum_url);
+            }
+            Verification::Unlisted if !checksum_required => {
+                warn!(
+                    "[{}] {} has no entry for {}; continuing unverified",
+                    section,
+                    checksum_url,
+                    checksum::asset_name(&hit.download_url)
+                );
+            }
+            Verification::Unlisted => {
+                return Err(anyhow!(
+                    "[{}] {} has no entry for {}",
+                    section,
+                    checksum_url,
+                    checksum::asset_name(&hit.download_url)
+                ));
+            }
+            Verification::Mismatch { expected, actual } => {
+                error!(
+                    "[{}] SHA-256 mismatch for {}: expected {}, got {}. Not installing.",
+                    section, &hit.download_url, expected, actual
+                );
+                return Ok(false);
+            }
+        }
+    } else if checksum_required {
+        return Err(anyhow!(
+            "[{}] checksum_anchor_text {:?} matched no link",
+            section,
+            conf.checksum_anchor_text.as_deref().unwrap_or_default()
+        ));
+    }
+
+    Ok(true)
+}
+
 /// Change file permissions to be executable. This only happens on
 /// posix; on Windows it does nothing.
 #[cfg(target_family = "unix")]
@@ -731,65 +1319,17 @@ fn set_executable(_path: &Path) -> Result<()> {
     Ok(())
 }
 
-fn parse_json(section: &str, conf: &Config, url: &str) -> Result<Option<Hit>> {
-    let mut attempts_remaining = 10;
-    let resp = loop {
-        if attempts_remaining == 0 {
-            return Err(anyhow!(format!("Failed to download {}", section)));
-        } else {
-            attempts_remaining -= 1;
+fn parse_json(section: &str, conf: &mut Config, url: &str, ctx: &RunContext) -> Result<Fetched> {
+    let page = ctx.pages.get_or_fetch(url, conf.validators.as_ref(), || {
+        fetch_page(section, conf, url, ctx)
+    })?;
+    Ok(match page {
+        Page::NotModified => Fetched::NotModified,
+        Page::Body { body, validators } => {
+            conf.validators = validators;
+            Fetched::Page(extract_data_from_json(body, conf)?)
         }
-
-        let agent = http_agent();
-        let resp = if let Ok(token) = std::env::var("GITHUB_TOKEN") {
-            let authorization_header_value = format!("token {token}");
-            agent.get(url)
-                    .header("Authorization", &authorization_header_value)
-                    .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/106.0.0.0 Safari/537.36")
-                    .call()
-        } else {
-            agent.get(url)
-                .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/106.0.0.0 Safari/537.36")
-                .call()
-        };
-
-        let response = match resp {
-            Ok(response) => response,
-            Err(_) => {
-                /* some kind of io/transport error */
-                let msg = format!("Unexpected error fetching {url}.");
-                return Err(anyhow!(msg));
-            }
-        };
-
-        let status_code = response.status().as_u16();
-        match status_code {
-            200..=299 => break response,
-            // https://developer.mozilla.org/en-US/docs/Web/HTTP/Status#client_error_responses
-            403 | 408 | 425 | 429 | 500 | 502 | 503 | 504 => {
-                let zzz = ((10 - attempts_remaining) * 4).min(60);
-                if status_code == 403 {
-                    let body = response.into_body().read_to_string()?;
-                    info!("Got 403: {body}");
-                }
-                info!("Got status {status_code} fetching {section}. Sleeping for {zzz} secs...");
-                std::thread::sleep(Duration::from_secs(zzz));
-                continue;
-            }
-            _ => {
-                let body = response.into_body().read_to_string()?;
-                let msg = format!(
-                    "Unexpected error fetching {url}. Status {status_code}. \
-                    Body: {body}"
-                );
-                return Err(anyhow!(msg));
-            }
-        };
-    };
-
-    let body = resp.into_body().read_to_string()?;
-    debug!("{}", &body);
-    extract_data_from_json(body, conf)
+    })
 }
 
 fn extract_data_from_json<T: AsRef<str>>(payload: T, conf: &Config) -> Result<Option<Hit>> {
@@ -800,13 +1340,16 @@ fn extract_data_from_json<T: AsRef<str>>(payload: T, conf: &Config) -> Result<Op
 
     let data = Value::from_str(payload.as_ref())?;
 
-    let vtag = conf.version_tag.clone().unwrap();
-    let version_str = data
-        .query(&vtag)?
-        .first()
-        .and_then(|v| v.as_str())
-        .unwrap_or("")
-        .to_string();
+    let version_str = match &conf.version_tag {
+        Some(vtag) => Some(
+            data.query(vtag)?
+                .first()
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string(),
+        ),
+        None => None,
+    };
 
     let commit_str = if let Some(ctag) = &conf.commit_tag {
         data.query(ctag)?
@@ -817,63 +1360,188 @@ fn extract_data_from_json<T: AsRef<str>>(payload: T, conf: &Config) -> Result<Op
         None
     };
 
-    let urls: Vec<String> = data
-        .query(&conf.anchor_tag)?
+    let anchors = data.query(&conf.anchor_tag)?;
+    // `digest_tag` selects one value per asset, in the same order as
+    // `anchor_tag`, so the two line up by index. If they don't (e.g. a
+    // selector that skips assets without a digest), ignore the digests
+    // rather than risk pairing a URL with another asset's hash.
+    let digests: Vec<Option<String>> = match &conf.digest_tag {
+        Some(dtag) => {
+            let found = data.query(dtag)?;
+            if found.len() == anchors.len() {
+                found
+                    .into_iter()
+                    .map(|v| v.as_str().and_then(checksum::parse_digest))
+                    .collect()
+            } else {
+                debug!(
+                    "digest_tag matched {} values but anchor_tag matched {}; ignoring digests",
+                    found.len(),
+                    anchors.len()
+                );
+                Vec::new()
+            }
+        }
+        None => Vec::new(),
+    };
+    // Unlike a digest, an asset can't do without its API URL once the
+    // section asked for one.
+    let api_urls: Vec<Option<String>> = match &conf.asset_url_tag {
+        Some(atag) => {
+            let found = data.query(atag)?;
+            if found.len() != anchors.len() {
+                return Err(anyhow!(
+                    "asset_url_tag matched {} values but anchor_tag matched {}",
+                    found.len(),
+                    anchors.len()
+                ));
+            }
+            found
+                .into_iter()
+                .map(|v| v.as_str().map(String::from))
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    let api_urls: HashMap<String, String> = anchors
+        .iter()
+        .zip(api_urls)
+        .filter_map(|(anchor, api_url)| Some((anchor.as_str()?.to_string(), api_url?)))
+        .collect();
+
+    let assets: Vec<(String, Option<String>)> = anchors
         .into_iter()
-        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+        .enumerate()
+        .filter_map(|(i, v)| {
+            v.as_str()
+                .map(|s| (s.to_string(), digests.get(i).cloned().flatten()))
+        })
         .collect();
+    let urls: Vec<String> = assets.iter().map(|(u, _)| u.clone()).collect();
 
     let re_pat = regex::Regex::new(&conf.anchor_text)?;
 
-    for u in urls {
-        if re_pat.is_match(&u) {
-            return Ok(Some(Hit {
-                version: version_str,
-                commit: commit_str,
-                download_url: u,
-            }));
+    let mut matching = assets.iter().filter(|(u, _)| re_pat.is_match(u)).peekable();
+    let first_match = matching.peek().map(|(u, _)| u.clone());
+    let found = match version_str {
+        Some(version) => matching.next().map(|asset| (version, asset)),
+        // Without a version_tag, each match names its own version.
+        None => highest_version(
+            conf.version_scheme,
+            matching.filter_map(|asset| Some((version_from_anchor(&re_pat, &asset.0)?, asset))),
+        ),
+    };
+    let Some((version, (u, digest))) = found else {
+        if let Some(u) = first_match {
+            warn!("Download link {} was found but has no version in it", u);
+        }
+        return Ok(None);
+    };
+    let checksum_url = match (&conf.checksum_url, &conf.checksum_anchor_text) {
+        (Some(url), _) => Some(url.clone()),
+        (None, Some(text)) => {
+            let checksum_pat = regex::Regex::new(text)?;
+            urls.iter().find(|c| checksum_pat.is_match(c)).cloned()
+        }
+        (None, None) => checksum::discover(&urls, u),
+    };
+    let signature_url = match (&conf.signature_url, &conf.signature_anchor_text) {
+        (Some(url), _) => Some(url.clone()),
+        (None, Some(text)) => {
+            let signature_pat = regex::Regex::new(text)?;
+            urls.iter().find(|c| signature_pat.is_match(c)).cloned()
+        }
+        (None, None) => conf.signature_key.as_ref().and_then(|key| {
+            let expected = format!("{}{}", u, key.signature_suffix());
+            urls.iter().find(|c| **c == expected).cloned()
+        }),
+    };
+    Ok(Some(Hit {
+        version,
+        commit: commit_str,
+        download_url: u.clone(),
+        checksum_url,
+        digest: digest.clone(),
+        signature_url,
+        api_urls,
+    }))
+}
+
+/// The candidate with the highest version under `scheme`. One the
+/// scheme can't order against the best so far doesn't replace it, so
+/// for `opaque` versions that's the first candidate.
+fn highest_version<T>(
+    scheme: VersionScheme,
+    candidates: impl IntoIterator<Item = (String, T)>,
+) -> Option<(String, T)> {
+    let mut best: Option<(String, T)> = None;
+    for (version, item) in candidates {
+        let higher = match &best {
+            Some((highest, _)) => scheme.compare(&version, highest) == Some(Ordering::Greater),
+            None => true,
+        };
+        if higher {
+            best = Some((version, item));
         }
     }
+    best
+}
 
-    Ok(None)
+/// Whether the `anchor_text` regex has a group named `version`.
+fn has_version_group(anchor_text: &str) -> Result<bool> {
+    Ok(regex::Regex::new(anchor_text)?
+        .capture_names()
+        .any(|name| name == Some("version")))
 }
 
-/// This function parses the target webpage trying to find two things:
-/// 1. The download link for the target binary
-/// 2. The version
-///
-/// To find the download link, we check all links on the page that
-/// match the selector given in `anchor_tag`. There could be many
-/// links (`<a>` tags) that match that anchor tag, and we'll keep
-/// checking all of these until we find one whose "text" value
-/// matches the regex given in the `anchor_text` field. This regex
-/// should be a complete match.
-fn parse_html_page(section: &str, conf: &Config, url: &str) -> Result<Option<Hit>> {
-    debug!("[{}] Fetching page at {}", section, &url);
+/// The `version` group of `anchor_text` (compiled as `re_pat`) in the
+/// text it matched, for sections without a `version_tag`.
+fn version_from_anchor(re_pat: &regex::Regex, text: &str) -> Option<String> {
+    let version = re_pat.captures(text)?.name("version")?.as_str().trim();
+    (!version.is_empty()).then(|| version.to_string())
+}
 
-    // Retry with backoff
-    let mut attempts_remaining = 10;
-    let resp = loop {
-        if attempts_remaining == 0 {
-            return Err(anyhow!(format!("Failed to download {}", section)));
-        } else {
-            attempts_remaining -= 1;
+/// Fetch `page_url`, retrying as `conf.retry` allows. The request is
+/// conditional if `conf.validators` is set.
+fn fetch_page(section: &str, conf: &Config, url: &str, ctx: &RunContext) -> Result<Page> {
+    debug!("[{}] Fetching page at {}", section, &url);
+    let policy = &conf.retry;
+    policy.run(section, url, || {
+        ctx.rate_limit.wait(section, url)?;
+        let mut request = ctx.credentials.apply(url, policy.apply(ctx.agent.get(url)));
+        if let Some(validators) = &conf.validators {
+            request = validators.apply(request);
         }
+        let resp = match request.call() {
+            Ok(resp) => resp,
+            Err(e) => return policy.transport_error(url, e),
+        };
 
-        let resp = http_agent().get(url)
-                .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/106.0.0.0 Safari/537.36")
-                .call()?;
         let status_code = resp.status().as_u16();
-
+        let rate_limited = ctx.rate_limit.observe(url, status_code, resp.headers());
         debug!("Fetching {section}, status: {status_code}");
         match status_code {
-            200..=299 => break resp,
-            // https://developer.mozilla.org/en-US/docs/Web/HTTP/Status#client_error_responses
-            403 | 408 | 425 | 429 | 500 | 502 | 503 | 504 => {
-                let zzz = ((10 - attempts_remaining) * 4).min(60);
-                info!("Got status {status_code} fetching {section}. Sleeping for {zzz} secs...");
-                std::thread::sleep(Duration::from_secs(zzz));
-                continue;
+            200..=299 => {
+                let validators = PageValidators::from_response(&resp);
+                let body = match resp.into_body().read_to_string() {
+                    Ok(body) => body,
+                    Err(e) => return policy.transport_error(url, e),
+                };
+                debug!("{}", &body);
+                Ok(Attempt::Done(Page::Body { body, validators }))
+            }
+            304 if conf.validators.is_some() => Ok(Attempt::Done(Page::NotModified)),
+            // `wait` at the top holds off until the reset.
+            403 | 429 if rate_limited => Ok(Attempt::RetryNow(format!("Got status {status_code}"))),
+            // GitHub's secondary rate limits answer 403 without saying
+            // when to come back, and the body is the only explanation.
+            403 if ratelimit::host_of(url) == "api.github.com" => {
+                let body = resp.into_body().read_to_string().unwrap_or_default();
+                info!("[{}] Got 403: {}", section, body);
+                Ok(Attempt::Retry(format!("Got status {status_code}")))
+            }
+            _ if policy.retries_status(status_code) => {
+                Ok(Attempt::Retry(format!("Got status {status_code}")))
             }
             _ => {
                 let body = resp.into_body().read_to_string()?;
@@ -881,16 +1549,45 @@ fn parse_html_page(section: &str, conf: &Config, url: &str) -> Result<Option<Hit
                     "Unexpected error fetching {url}. Status {status_code}. \
                     Body: {body}"
                 );
-                return Err(anyhow!(msg));
+                Err(anyhow!(msg))
             }
-        };
-    };
+        }
+    })
+}
 
-    let body = resp.into_body().read_to_string()?;
-    debug!("{}", &body);
+/// This function parses the target webpage trying to find two things:
+/// 1. The download link for the target binary
+/// 2. The version
+///
+/// To find the download link, we check all links on the page that
+/// match the selector given in `anchor_tag`. There could be many
+/// links (`<a>` tags) that match that anchor tag, and we'll keep
+/// checking all of these until we find one whose "text" value
+/// matches the regex given in the `anchor_text` field. This regex
+/// should be a complete match.
+fn parse_html_page(
+    section: &str,
+    conf: &mut Config,
+    url: &str,
+    ctx: &RunContext,
+) -> Result<Fetched> {
+    let page = ctx.pages.get_or_fetch(url, conf.validators.as_ref(), || {
+        fetch_page(section, conf, url, ctx)
+    })?;
+    Ok(match page {
+        Page::NotModified => Fetched::NotModified,
+        Page::Body { body, validators } => {
+            conf.validators = validators;
+            Fetched::Page(scrape_html_page(section, conf, url, &body)?)
+        }
+    })
+}
 
+/// Find the download link and version in a fetched page; see
+/// `parse_html_page`.
+fn scrape_html_page(section: &str, conf: &Config, url: &str, body: &str) -> Result<Option<Hit>> {
     debug!("[{}] Setting up parsers", section);
-    let fragment = Html::parse_document(&body);
+    let fragment = Html::parse_document(body);
     let stories = match Selector::parse(&conf.anchor_tag) {
         Ok(s) => s,
         Err(e) => {
@@ -898,29 +1595,28 @@ fn parse_html_page(section: &str, conf: &Config, url: &str) -> Result<Option<Hit
             return Ok(None);
         }
     };
-    let versions = Selector::parse(conf.version_tag.as_ref().unwrap()).unwrap();
+    let versions = conf
+        .version_tag
+        .as_ref()
+        .map(|tag| {
+            Selector::parse(tag).map_err(|e| {
+                anyhow!(
+                    "[{}] Invalid version_tag selector \"{}\": {:?}",
+                    section,
+                    tag,
+                    e
+                )
+            })
+        })
+        .transpose()?;
     let re_pat = regex::Regex::new(format!("^{}$", &conf.anchor_text).as_str())?;
 
     debug!("[{}] Looking for matches...", section);
+    let mut matches = Vec::new();
     for story in fragment.select(&stories) {
         if let Some(href) = &story.value().attr("href") {
             // This is the download target in the matched link
-            let download_url = if href.starts_with("http") {
-                // Absolute path
-                href.to_string()
-            } else if href.starts_with('/') || href.starts_with("../") {
-                // Relative to domain
-                let mut u = Url::parse(url)?;
-                u.set_query(None);
-                u.path_segments_mut().unwrap().clear();
-                format!("{}", u.join(href)?)
-            } else {
-                // Relative to page url
-                let mut u = Url::parse(url)?;
-                u.set_query(None);
-                //u.path_segments_mut().unwrap().clear();
-                format!("{}", u.join(href)?)
-            };
+            let download_url = resolve_href(url, href)?;
 
             debug!("[{}] possible download_url?: {}", section, &download_url);
 
@@ -932,29 +1628,133 @@ fn parse_html_page(section: &str, conf: &Config, url: &str) -> Result<Option<Hit
                 continue;
             }
             debug!("[{}] Found a match for anchor_text: {}", section, link_text);
+            matches.push((download_url, link_text.to_string()));
+            // With a version_tag, the first match is the download.
+            if versions.is_some() {
+                break;
+            }
+        }
+    }
+    let Some((first_url, _)) = matches.first() else {
+        warn!("[{}] Matched nothing at url {}", section, url);
+        return Ok(None);
+    };
 
-            return if let Some(raw_version) = fragment.select(&versions).next() {
-                let version = raw_version.text().join("").trim().to_string();
-                info!("[{}] Found a match on versions tag: {}", section, version);
-                Ok(Some(Hit {
-                    version,
-                    // TODO: implement commit tracking for HTML page extraction
-                    commit: None,
-                    download_url,
-                }))
-            } else {
-                warn!(
-                    "[{}] Download link {} was found but failed to match version \
-                     tag \"{}\"",
-                    section,
-                    &download_url,
-                    conf.version_tag.as_ref().unwrap()
-                );
-                Ok(None)
-            };
+    let found = match &versions {
+        Some(versions) => fragment
+            .select(versions)
+            .next()
+            .map(|v| (v.text().join("").trim().to_string(), first_url.clone())),
+        // Without one, every match names its own version: a directory
+        // listing has them all, usually oldest first.
+        None => highest_version(
+            conf.version_scheme,
+            matches.iter().filter_map(|(download_url, link_text)| {
+                Some((
+                    version_from_anchor(&re_pat, link_text)?,
+                    download_url.clone(),
+                ))
+            }),
+        ),
+    };
+    let Some((version, download_url)) = found else {
+        match &conf.version_tag {
+            Some(tag) => warn!(
+                "[{}] Download link {} was found but failed to match version \
+                 tag \"{}\"",
+                section, first_url, tag
+            ),
+            None => warn!(
+                "[{}] Download link {} was found but has no version in it",
+                section, first_url
+            ),
+        }
+        return Ok(None);
+    };
+    info!("[{}] Found a match on versions tag: {}", section, version);
+    let checksum_url = match (&conf.checksum_url, &conf.checksum_anchor_text) {
+        (Some(u), _) => Some(u.clone()),
+        (None, Some(text)) => find_link_by_text(&fragment, &stories, url, text)?,
+        (None, None) => None,
+    };
+    let signature_url = match (&conf.signature_url, &conf.signature_anchor_text) {
+        (Some(u), _) => Some(u.clone()),
+        (None, Some(text)) => find_link_by_text(&fragment, &stories, url, text)?,
+        (None, None) => None,
+    };
+    Ok(Some(Hit {
+        version,
+        commit: find_commit(section, &fragment, conf)?,
+        download_url,
+        checksum_url,
+        digest: None,
+        signature_url,
+        api_urls: HashMap::new(),
+    }))
+}
+
+/// The commit that `commit_tag` selects on an HTML page, if the section
+/// has one: the text of the first matching tag, or the value of its
+/// `commit_attribute`.
+fn find_commit(section: &str, fragment: &Html, conf: &Config) -> Result<Option<String>> {
+    let Some(tag) = &conf.commit_tag else {
+        return Ok(None);
+    };
+    let selector = Selector::parse(tag)
+        .map_err(|e| anyhow!("[{}] Invalid commit_tag \"{}\": {:?}", section, tag, e))?;
+    let commit = fragment
+        .select(&selector)
+        .next()
+        .and_then(|element| match &conf.commit_attribute {
+            Some(attribute) => element.value().attr(attribute).map(String::from),
+            None => Some(element.text().join("")),
+        })
+        .map(|commit| commit.trim().to_string())
+        .filter(|commit| !commit.is_empty());
+    if commit.is_none() {
+        warn!("[{}] Failed to match commit tag \"{}\"", section, tag);
+    }
+    Ok(commit)
+}
+
+/// Turn an `href` found on the page at `page_url` into an absolute URL.
+fn resolve_href(page_url: &str, href: &str) -> Result<String> {
+    Ok(if href.starts_with("http") {
+        // Absolute path
+        href.to_string()
+    } else if href.starts_with('/') || href.starts_with("../") {
+        // Relative to domain
+        let mut u = Url::parse(page_url)?;
+        u.set_query(None);
+        u.path_segments_mut().unwrap().clear();
+        format!("{}", u.join(href)?)
+    } else {
+        // Relative to page url
+        let mut u = Url::parse(page_url)?;
+        u.set_query(None);
+        //u.path_segments_mut().unwrap().clear();
+        format!("{}", u.join(href)?)
+    })
+}
+
+/// The absolute URL of the first link matched by `selector` whose text
+/// fully matches the regex `text`, the same way `anchor_text` is matched.
+fn find_link_by_text(
+    fragment: &Html,
+    selector: &Selector,
+    page_url: &str,
+    text: &str,
+) -> Result<Option<String>> {
+    let re_pat = regex::Regex::new(format!("^{}$", text).as_str())?;
+    for link in fragment.select(selector) {
+        let Some(href) = link.value().attr("href") else {
+            continue;
+        };
+        let link_text = link.text().collect::<Vec<_>>().join(" ");
+        if re_pat.is_match(link_text.trim()) {
+            return Ok(Some(resolve_href(page_url, href)?));
         }
     }
-    warn!("[{}] Matched nothing at url {}", section, url);
     Ok(None)
 }
 
@@ -1305,11 +2105,115 @@ mod tests {
             version : "13.0.0".to_string(),
             download_url : "https://github.com/BurntSushi/ripgrep/releases/download/13.0.0/ripgrep-13.0.0-x86_64-unknown-linux-musl.tar.gz".to_string(),
             commit: None,
+            checksum_url: None,
+            digest: None,
+            signature_url: None,
+            api_urls: HashMap::new(),
         };
         assert_eq!(out, Some(expected_hit));
         Ok(())
     }
 
+    #[test]
+    fn extract_data_from_json_discovers_checksum_asset() -> Result<()> {
+        let payload = r#"{
+            "tag_name": "v0.60.3",
+            "assets": [
+                {"browser_download_url": "https://example.com/d/fzf-0.60.3-linux_amd64.tar.gz"},
+                {"browser_download_url": "https://example.com/d/fzf_0.60.3_checksums.txt"}
+            ]
+        }"#;
+        let mut conf = Config {
+            anchor_tag: "$.assets.*.browser_download_url".to_string(),
+            anchor_text: r"fzf-(\d+\.\d+\.\d+)-linux_amd64.tar.gz".to_string(),
+            version_tag: Some("$.tag_name".to_string()),
+            ..Default::default()
+        };
+        let hit = extract_data_from_json(payload, &conf)?.unwrap();
+        assert_eq!(
+            hit.checksum_url.as_deref(),
+            Some("https://example.com/d/fzf_0.60.3_checksums.txt")
+        );
+
+        // An explicit pattern that matches nothing leaves the hit without
+        // a checksum URL; `process` then refuses to install.
+        conf.checksum_anchor_text = Some(r"SHA256SUMS".to_string());
+        let hit = extract_data_from_json(payload, &conf)?.unwrap();
+        assert_eq!(hit.checksum_url, None);
+        Ok(())
+    }
+
+    #[test]
+    fn extract_data_from_json_pairs_digest_with_selected_asset() -> Result<()> {
+        let payload = r#"{
+            "tag_name": "v1.9.1",
+            "assets": [
+                {
+                    "browser_download_url": "https://example.com/d/starship-aarch64-apple-darwin.tar.gz",
+                    "digest": "sha256:1111111111111111111111111111111111111111111111111111111111111111"
+                },
+                {
+                    "browser_download_url": "https://example.com/d/starship-x86_64-unknown-linux-musl.tar.gz",
+                    "digest": "sha256:2222222222222222222222222222222222222222222222222222222222222222"
+                },
+                {
+                    "browser_download_url": "https://example.com/d/starship.sha256",
+                    "digest": null
+                }
+            ]
+        }"#;
+        let conf = Config {
+            anchor_tag: "$.assets.*.browser_download_url".to_string(),
+            anchor_text: r"starship-x86_64-unknown-linux-musl.tar.gz".to_string(),
+            version_tag: Some("$.tag_name".to_string()),
+            digest_tag: Some("$.assets.*.digest".to_string()),
+            ..Default::default()
+        };
+        let hit = extract_data_from_json(payload, &conf)?.unwrap();
+        assert_eq!(hit.digest.as_deref(), Some(&"2".repeat(64)[..]));
+        Ok(())
+    }
+
+    #[test]
+    fn extract_data_from_json_pairs_api_url_with_each_asset() -> Result<()> {
+        let payload = r#"{
+            "tag_name": "v2.0.0",
+            "assets": [
+                {
+                    "browser_download_url": "https://github.com/o/r/releases/download/v2.0.0/tool-linux.tar.gz",
+                    "url": "https://api.github.com/repos/o/r/releases/assets/1"
+                },
+                {
+                    "browser_download_url": "https://github.com/o/r/releases/download/v2.0.0/SHA256SUMS",
+                    "url": "https://api.github.com/repos/o/r/releases/assets/2"
+                }
+            ]
+        }"#;
+        let mut conf = Config {
+            anchor_tag: "$.assets.*.browser_download_url".to_string(),
+            anchor_text: r"tool-linux.tar.gz".to_string(),
+            version_tag: Some("$.tag_name".to_string()),
+            asset_url_tag: Some("$.assets.*.url".to_string()),
+            checksum_anchor_text: Some("SHA256SUMS".to_string()),
+            ..Default::default()
+        };
+        let hit = extract_data_from_json(payload, &conf)?.unwrap();
+        let checksum_url = hit.checksum_url.as_deref().unwrap();
+        assert_eq!(
+            hit.api_urls.get(checksum_url).map(String::as_str),
+            Some("https://api.github.com/repos/o/r/releases/assets/2")
+        );
+        assert_eq!(
+            hit.api_urls.get(&hit.download_url).map(String::as_str),
+            Some("https://api.github.com/repos/o/r/releases/assets/1")
+        );
+
+        conf.asset_url_tag = Some("$.assets[0].url".to_string());
+        let err = extract_data_from_json(payload, &conf).unwrap_err();
+        assert!(err.to_string().contains("asset_url_tag"), "{err}");
+        Ok(())
+    }
+
     fn ini_map<const N: usize>(pairs: [(&str, &str); N]) -> HashMap<String, String> {
         pairs
             .into_iter()
@@ -1317,6 +2221,75 @@ mod tests {
             .collect()
     }
 
+    fn templates<const N: usize>(defs: [(&str, HashMap<String, String>); N]) -> Templates {
+        defs.into_iter()
+            .map(|(name, fields)| (name.to_string(), fields))
+            .collect()
+    }
+
+    #[test]
+    fn templates_build_on_parents_and_merge_in_order() -> Result<()> {
+        let templates = templates([
+            (
+                "base",
+                ini_map([
+                    ("page_url", "https://example.com/{project}/"),
+                    ("anchor_tag", "a"),
+                    ("max_attempts", "2"),
+                ]),
+            ),
+            (
+                "musl",
+                ini_map([
+                    ("template", "base"),
+                    ("anchor_text", "{project}-x86_64-unknown-linux-musl.tar.gz"),
+                    ("max_attempts", "3"),
+                ]),
+            ),
+            (
+                "mirror",
+                ini_map([("page_url", "https://mirror.example.com/{project}/")]),
+            ),
+        ]);
+        let section = ini_map([
+            ("template", "musl, mirror"),
+            ("project", "tool"),
+            ("anchor_tag", "main a"),
+        ]);
+        let values = resolve_templates("tool", &templates, &section)?;
+        // Every field is copied, later templates win, and the section wins over all.
+        assert_eq!(values["page_url"], "https://mirror.example.com/{project}/");
+        assert_eq!(
+            values["anchor_text"],
+            "{project}-x86_64-unknown-linux-musl.tar.gz"
+        );
+        assert_eq!(values["max_attempts"], "3");
+        assert_eq!(values["anchor_tag"], "main a");
+        assert_eq!(values["template"], "musl, mirror");
+        Ok(())
+    }
+
+    #[test]
+    fn template_cycles_and_missing_templates_are_errors() {
+        let templates = templates([
+            ("a", ini_map([("template", "b")])),
+            ("b", ini_map([("template", "a")])),
+            ("c", ini_map([("template", "d")])),
+        ]);
+        let err = resolve_templates("tool", &templates, &ini_map([("template", "a")]))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("[tool]") && err.contains("a -> b -> a"),
+            "{err}"
+        );
+
+        let err = resolve_templates("tool", &templates, &ini_map([("template", "c")]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("'d' was not found"), "{err}");
+    }
+
     #[test]
     fn singular_target_defaults_to_section_name() {
         let targets = build_extraction_targets("ripgrep", &ini_map([])).unwrap();
@@ -1524,7 +2497,7 @@ mod tests {
     #[test]
     fn extract_target_from_zipfile_pulls_each_listed_file() {
         let dir = tempfile::tempdir().unwrap();
-        let mut zip_bytes = build_test_zip(&[
+        let zip_bytes = build_test_zip(&[
             ("rg", b"binary"),
             ("doc/rg.1", b"manpage"),
             ("complete/rg.bash", b"completion"),
@@ -1539,8 +2512,12 @@ mod tests {
             )],
         );
 
-        let written =
-            crate::zipfile::extract_target_from_zipfile(&mut zip_bytes, &conf, dir.path()).unwrap();
+        let written = crate::zipfile::extract_target_from_zipfile(
+            std::io::Cursor::new(zip_bytes),
+            &conf,
+            dir.path(),
+        )
+        .unwrap();
 
         let written_set: std::collections::HashSet<PathBuf> = written.into_iter().collect();
         assert_eq!(written_set.len(), 3);
@@ -1556,4 +2533,414 @@ mod tests {
         );
         assert!(!dir.path().join("README.md").exists());
     }
+
+    #[test]
+    fn version_comes_from_anchor_text_without_version_tag() -> Result<()> {
+        // A directory listing: nothing but the links carries the version,
+        // and the newest release comes last.
+        let body = r#"<pre><a href="../">../</a>
+            <a href="tool-1.2.0-linux.tar.gz">tool-1.2.0-linux.tar.gz</a>
+            <a href="tool-1.9.0-linux.tar.gz">tool-1.9.0-linux.tar.gz</a>
+            <a href="tool-1.10.2-linux.tar.gz">tool-1.10.2-linux.tar.gz</a>
+            <a href="tool-1.10.2-linux.tar.gz.sha256">tool-1.10.2-linux.tar.gz.sha256</a></pre>"#;
+        let mut conf = Config::new();
+        conf.anchor_tag = "a".to_string();
+        conf.anchor_text = r"tool-(?P<version>[\d.]+)-linux.tar.gz".to_string();
+        let hit = scrape_html_page("tool", &conf, "https://example.com/dl/", body)?.unwrap();
+        assert_eq!(hit.version, "1.10.2");
+        assert_eq!(
+            hit.download_url,
+            "https://example.com/dl/tool-1.10.2-linux.tar.gz"
+        );
+
+        let payload = r#"{"files": [
+            "https://example.com/dl/tool-2.0.1-linux.tar.gz",
+            "https://example.com/dl/tool-2.0.10-linux.tar.gz",
+            "https://example.com/dl/tool-2.0.9-linux.tar.gz"
+        ]}"#;
+        conf.anchor_tag = "$.files.*".to_string();
+        let hit = extract_data_from_json(payload, &conf)?.unwrap();
+        assert_eq!(hit.version, "2.0.10");
+        assert_eq!(
+            hit.download_url,
+            "https://example.com/dl/tool-2.0.10-linux.tar.gz"
+        );
+
+        assert!(has_version_group(&conf.anchor_text)?);
+        assert!(!has_version_group(r"tool-(\d+)-linux.tar.gz")?);
+        Ok(())
+    }
+
+    #[test]
+    fn bad_version_tag_selector_is_an_error() {
+        let mut conf = Config::new();
+        conf.anchor_tag = "a".to_string();
+        conf.anchor_text = "tool.tar.gz".to_string();
+        conf.version_tag = Some("b[".to_string());
+        let body = r#"<a href="tool.tar.gz">tool.tar.gz</a><b>1.0</b>"#;
+        let err = scrape_html_page("tool", &conf, "https://example.com/", body)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("[tool] Invalid version_tag selector"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn html_commit_tag_detects_a_rebuilt_tag() -> Result<()> {
+        let commit = std::sync::Arc::new(std::sync::Mutex::new("abc123"));
+        let current = commit.clone();
+        let url = crate::testutil::http_stand_in(move |head| {
+            let body = match head.split_whitespace().nth(1).unwrap_or_default() {
+                "/releases/" => format!(
+                    r#"<a href="/dl/tool-nightly">tool-nightly</a><b>nightly</b>
+                    <a class="commit" href="/commit/{0}">{0}</a>"#,
+                    current.lock().unwrap()
+                ),
+                _ => "binary".to_string(),
+            };
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .into_bytes()
+        });
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("tool"), "old binary")?;
+        let mut conf = make_conf_from_ini("tool", &[]);
+        conf.page_url = format!("{url}/releases/");
+        conf.anchor_tag = "a".to_string();
+        conf.anchor_text = "tool-nightly".to_string();
+        conf.version_tag = Some("b".to_string());
+        conf.version_scheme = "opaque".parse()?;
+        conf.commit_tag = Some("a.commit".to_string());
+        conf.version = Some("nightly".to_string());
+        conf.commit = Some("abc123".to_string());
+
+        // Each `process` call needs a fresh context, or the page comes
+        // from the cache.
+        let outcome = process("tool", &mut conf, dir.path(), false, &RunContext::new())?;
+        assert!(matches!(outcome, Outcome::UpToDate { .. }));
+
+        *commit.lock().unwrap() = "def456";
+        let outcome = process("tool", &mut conf, dir.path(), false, &RunContext::new())?;
+        let Outcome::Updated { commit, .. } = outcome else {
+            panic!("expected an update");
+        };
+        assert_eq!(commit.as_deref(), Some("def456"));
+        assert_eq!(std::fs::read_to_string(dir.path().join("tool"))?, "binary");
+
+        let fragment = Html::parse_document(r#"<a class="commit" href="/commit/def456">x</a>"#);
+        conf.commit_attribute = Some("href".to_string());
+        assert_eq!(
+            find_commit("tool", &fragment, &conf)?.as_deref(),
+            Some("/commit/def456")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unchanged_page_is_not_modified() -> Result<()> {
+        let url = crate::testutil::http_stand_in(|head| {
+            if head.to_ascii_lowercase().contains("if-none-match: \"v1\"") {
+                b"HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_vec()
+            } else {
+                let body = r#"<a href="/tool-1.2.tar.gz">tool-1.2.tar.gz</a><b>1.2</b>"#;
+                format!(
+                    "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\
+                     Connection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .into_bytes()
+            }
+        }) + "/downloads/";
+        let mut conf = Config::new();
+        conf.anchor_tag = "a".to_string();
+        conf.anchor_text = "tool-.*".to_string();
+        conf.version_tag = Some("b".to_string());
+
+        let ctx = RunContext::new();
+        let Fetched::Page(Some(hit)) = parse_html_page("tool", &mut conf, &url, &ctx)? else {
+            panic!("expected a hit");
+        };
+        assert_eq!(hit.version, "1.2");
+        assert!(conf.validators.is_some());
+
+        assert!(matches!(
+            parse_html_page("tool", &mut conf, &url, &ctx)?,
+            Fetched::NotModified
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn private_assets_download_from_their_api_url() -> Result<()> {
+        use std::sync::{Arc, Mutex};
+
+        let base = Arc::new(Mutex::new(String::new()));
+        let server_base = base.clone();
+        let url = crate::testutil::http_stand_in(move |head| {
+            let base = server_base.lock().unwrap().clone();
+            let path = head.split_whitespace().nth(1).unwrap_or_default();
+            let octet_stream = head
+                .to_ascii_lowercase()
+                .contains("accept: application/octet-stream");
+            let (status, body) = match path {
+                "/repos/o/r/releases/latest" => (
+                    "200 OK",
+                    format!(
+                        r#"{{"tag_name": "v2.0.0", "assets": [{{
+                            "browser_download_url": "{base}/o/r/releases/download/v2.0.0/tool-linux",
+                            "url": "{base}/repos/o/r/releases/assets/7"
+                        }}]}}"#
+                    ),
+                ),
+                "/repos/o/r/releases/assets/7" if octet_stream => {
+                    ("200 OK", "private bytes".to_string())
+                }
+                // Without the header, the API describes the asset.
+                "/repos/o/r/releases/assets/7" => ("200 OK", r#"{"id": 7}"#.to_string()),
+                _ => ("404 Not Found", String::new()),
+            };
+            format!(
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .into_bytes()
+        });
+        *base.lock().unwrap() = url.clone();
+
+        let dir = tempfile::tempdir()?;
+        let mut conf = make_conf_from_ini(
+            "tool",
+            &[("target_filename_to_extract_from_archive", "tool")],
+        );
+        conf.method = "api_json".to_string();
+        conf.page_url = format!("{url}/repos/o/r/releases/latest");
+        conf.anchor_tag = "$.assets.*.browser_download_url".to_string();
+        conf.anchor_text = "tool-linux".to_string();
+        conf.version_tag = Some("$.tag_name".to_string());
+        conf.asset_url_tag = Some("$.assets.*.url".to_string());
+
+        let outcome = process("tool", &mut conf, dir.path(), false, &RunContext::new())?;
+        assert!(outcome.updated());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("tool"))?,
+            "private bytes"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn github_api_403_is_retried() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let seen = requests.clone();
+        let proxy = crate::testutil::http_stand_in(move |head| {
+            if head.starts_with("CONNECT ") {
+                return b"HTTP/1.1 200 Connection established\r\n\r\n".to_vec();
+            }
+            let (status, body) = match seen.fetch_add(1, Ordering::SeqCst) {
+                0 => ("403 Forbidden", "secondary rate limit"),
+                _ => ("200 OK", "{}"),
+            };
+            format!(
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .into_bytes()
+        });
+        let mut ctx = RunContext::new();
+        ctx.set_network(&NetworkSettings {
+            proxy: Some(proxy),
+            ..Default::default()
+        })?;
+        let mut conf = Config::new();
+        conf.retry.backoff = std::time::Duration::ZERO;
+
+        let url = "http://api.github.com/repos/o/r/releases/latest";
+        let Page::Body { body, .. } = fetch_page("tool", &conf, url, &ctx)? else {
+            panic!("expected a body");
+        };
+        assert_eq!(body, "{}");
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Elsewhere a 403 is final, and says why.
+        requests.store(0, Ordering::SeqCst);
+        let err = fetch_page("tool", &conf, "http://tools.example/", &ctx)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("secondary rate limit"), "{err}");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn platforms_run_separately_from_one_fetch() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let page_fetches = Arc::new(AtomicUsize::new(0));
+        let fetches = page_fetches.clone();
+        let url = crate::testutil::http_stand_in(move |head| {
+            let path = head.split_whitespace().nth(1).unwrap_or_default();
+            let host = head
+                .lines()
+                .find_map(|l| l.strip_prefix("host: ").or(l.strip_prefix("Host: ")))
+                .unwrap_or_default();
+            let body = match path {
+                "/releases/latest" => {
+                    fetches.fetch_add(1, Ordering::SeqCst);
+                    format!(
+                        r#"{{"tag_name": "v1.0.0", "assets": [
+                            {{"url": "http://{host}/dl/tool-linux-x86_64"}},
+                            {{"url": "http://{host}/dl/tool-windows-x86_64.exe"}}
+                        ]}}"#
+                    )
+                }
+                _ => format!("bytes for {path}"),
+            };
+            format!(
+                "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .into_bytes()
+        });
+
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join("lifter.config");
+        let page_url = format!("{url}/releases/latest");
+        std::fs::write(
+            &config_path,
+            format!(
+                "[tool]\n\
+                 platforms = linux-x86_64, windows-x86_64\n\
+                 method = api_json\n\
+                 page_url = {url}/releases/latest\n\
+                 version_tag = $.tag_name\n\
+                 anchor_tag = $.assets.*.url\n\
+                 anchor_text = .*/tool-{{os}}-{{arch}}{{exe_suffix}}\n\
+                 version@windows-x86_64 = v0.9.0\n"
+            ),
+        )?;
+        let conf = tini::Ini::from_file(&config_path)?;
+        let templates = Templates::new();
+        let runs = SectionRun::expand("tool", &templates, &conf);
+        assert_eq!(
+            runs.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(),
+            ["tool@linux-x86_64", "tool@windows-x86_64"]
+        );
+
+        let validators_path = dir.path().join(".lifter-validators.json");
+        let ctx = RunContext {
+            validators: Some(ValidatorStore::load(&validators_path)),
+            ..RunContext::new()
+        };
+        let filename = config_path.to_string_lossy();
+        for run in &runs {
+            run_section(run, &templates, &conf, &filename, dir.path(), false, &ctx);
+        }
+        assert_eq!(page_fetches.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("tool-linux-x86_64"))?,
+            "bytes for /dl/tool-linux-x86_64"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("tool-windows-x86_64.exe"))?,
+            "bytes for /dl/tool-windows-x86_64.exe"
+        );
+        let updated = tini::Ini::from_file(&config_path)?;
+        for platform in ["linux-x86_64", "windows-x86_64"] {
+            let version: Option<String> = updated.get("tool", &format!("version@{platform}"));
+            assert_eq!(version.as_deref(), Some("v1.0.0"));
+        }
+        assert_eq!(updated.get::<String>("tool", "version"), None);
+
+        // Each platform keeps its own validators, as separate sections would.
+        let entries: std::collections::BTreeMap<String, serde_json::Value> =
+            serde_json::from_str(&std::fs::read_to_string(&validators_path)?)?;
+        assert_eq!(
+            entries.keys().collect::<Vec<_>>(),
+            ["tool@linux-x86_64", "tool@windows-x86_64"]
+        );
+        for entry in entries.values() {
+            assert_eq!(entry["page_url"], page_url.as_str());
+            assert_eq!(entry["version"], "v1.0.0");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn editing_anchor_text_forces_a_full_fetch() -> Result<()> {
+        use std::sync::{Arc, Mutex};
+
+        // Whether each page request was conditional.
+        let conditional = Arc::new(Mutex::new(Vec::new()));
+        let log = conditional.clone();
+        let url = crate::testutil::http_stand_in(move |head| {
+            let path = head.split_whitespace().nth(1).unwrap_or_default();
+            let body = if path == "/downloads/" {
+                let etag_matches = head.to_ascii_lowercase().contains("if-none-match: \"v1\"");
+                log.lock().unwrap().push(etag_matches);
+                if etag_matches {
+                    return b"HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_vec();
+                }
+                r#"<a href="/tool-linux">tool-linux</a><b>1.2</b>"#
+            } else {
+                "binary"
+            };
+            format!(
+                "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .into_bytes()
+        });
+
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join("lifter.config");
+        std::fs::write(
+            &config_path,
+            format!(
+                "[tool]\n\
+                 page_url = {url}/downloads/\n\
+                 anchor_tag = a\n\
+                 anchor_text = tool-.*\n\
+                 version_tag = b\n"
+            ),
+        )?;
+        let filename = config_path.to_string_lossy();
+        // A new context each time, as each run of lifter has, so the
+        // page isn't served from the cache.
+        let run = |conf: &tini::Ini| {
+            let ctx = RunContext {
+                validators: Some(ValidatorStore::load(
+                    &dir.path().join(".lifter-validators.json"),
+                )),
+                ..RunContext::new()
+            };
+            let templates = Templates::new();
+            for run in SectionRun::expand("tool", &templates, conf) {
+                run_section(&run, &templates, conf, &filename, dir.path(), false, &ctx);
+            }
+        };
+
+        run(&tini::Ini::from_file(&config_path)?);
+        assert_eq!(std::fs::read_to_string(dir.path().join("tool"))?, "binary");
+        run(&tini::Ini::from_file(&config_path)?);
+        assert_eq!(*conditional.lock().unwrap(), [false, true]);
+
+        let edited = std::fs::read_to_string(&config_path)?
+            .replace("anchor_text = tool-.*", "anchor_text = tool-linux");
+        std::fs::write(&config_path, edited)?;
+        run(&tini::Ini::from_file(&config_path)?);
+        assert_eq!(*conditional.lock().unwrap(), [false, true, false]);
+        Ok(())
+    }
 }
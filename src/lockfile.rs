@@ -0,0 +1,246 @@
+//! Installed state, kept apart from the config.
+//!
+//! Without a lock file, lifter records each new `version` (and
+//! `commit`) back into the section it came from, which rewrites a
+//! hand-maintained, often version-controlled, config on every update.
+//! With one, the config only says *what* to install and `lifter.lock`
+//! says what *is* installed:
+//!
+//! ```json
+//! {
+//!   "lock_version": 1,
+//!   "sections": {
+//!     "ripgrep": {
+//!       "version": "14.1.1",
+//!       "download_url": "https://github.com/.../ripgrep-14.1.1-x86_64-unknown-linux-musl.tar.gz",
+//!       "sha256": "4cf9f2741e6c465ffdb7c26f38056a59e2a2544b51f7cc128ef28337eeae4d8e",
+//!       "size": 2566310,
+//!       "updated_at": "2026-04-21T14:23:49Z"
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! An entry in the lock file takes precedence over an inline `version`
+//! in the config. Sections without an entry fall back to the inline
+//! value, so an existing config keeps working and is migrated one
+//! update at a time — or all at once with `lifter lock`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::configfile::write_atomic;
+use crate::credentials::CREDENTIALS_PREFIX;
+use crate::network::SETTINGS_SECTION;
+
+/// Bumped when the layout changes incompatibly.
+const LOCK_VERSION: u32 = 1;
+
+/// What was installed for one section.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockEntry {
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// The remaining fields are unknown for entries migrated from an
+    /// inline `version`, until that section next updates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    /// Hex SHA-256 of the downloaded artifact (the archive, not what
+    /// was extracted from it).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Size of the downloaded artifact in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// UTC RFC 3339, second precision.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LockData {
+    lock_version: u32,
+    #[serde(default)]
+    sections: BTreeMap<String, LockEntry>,
+}
+
+/// A lock file on disk plus its parsed entries. Sections run in
+/// parallel, so every update rewrites the whole file under one mutex.
+pub struct LockFile {
+    path: PathBuf,
+    sections: Mutex<BTreeMap<String, LockEntry>>,
+}
+
+impl LockFile {
+    /// Read the lock file at `path`. A missing file is an empty lock;
+    /// it is created on the first write.
+    pub fn load(path: &Path) -> Result<LockFile> {
+        let sections = match std::fs::read_to_string(path) {
+            Ok(text) => parse(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(anyhow!("Failed to read {}: {}", path.display(), e)),
+        };
+        Ok(LockFile {
+            path: path.to_path_buf(),
+            sections: Mutex::new(sections),
+        })
+    }
+
+    pub fn get(&self, section: &str) -> Option<LockEntry> {
+        self.sections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(section)
+            .cloned()
+    }
+
+    /// Record a new install of `section` and write the file.
+    pub fn record(&self, section: &str, entry: LockEntry) -> Result<()> {
+        let mut sections = self.sections.lock().unwrap_or_else(PoisonError::into_inner);
+        sections.insert(section.to_string(), entry);
+        self.save(&sections)
+    }
+
+    /// Seed the lock from the inline `version`/`commit` of every
+    /// section in `conf` that doesn't have an entry yet, and write the
+    /// file (creating it, even if there was nothing to copy). Returns
+    /// the names of the sections that were added.
+    pub fn migrate_from(&self, conf: &tini::Ini) -> Result<Vec<String>> {
+        let mut sections = self.sections.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut added = Vec::new();
+        for (name, section) in conf.iter() {
+            if name.starts_with("template:")
+                || name == SETTINGS_SECTION
+                || name.starts_with(CREDENTIALS_PREFIX)
+            {
+                continue;
+            }
+            // A section with `platforms` has a `version@<platform>`
+            // for each, and a lock entry `<section>@<platform>`.
+            for (key, version) in section.iter() {
+                let entry_name = match key.strip_prefix("version") {
+                    Some("") => name.clone(),
+                    Some(platform) if platform.starts_with('@') => format!("{name}{platform}"),
+                    _ => continue,
+                };
+                if sections.contains_key(&entry_name) {
+                    continue;
+                }
+                let commit_key = format!("commit{}", &key["version".len()..]);
+                sections.insert(
+                    entry_name.clone(),
+                    LockEntry {
+                        version: version.clone(),
+                        commit: section.get(&commit_key),
+                        download_url: None,
+                        sha256: None,
+                        size: None,
+                        updated_at: None,
+                    },
+                );
+                added.push(entry_name);
+            }
+        }
+        self.save(&sections)?;
+        Ok(added)
+    }
+
+    fn save(&self, sections: &BTreeMap<String, LockEntry>) -> Result<()> {
+        let data = LockData {
+            lock_version: LOCK_VERSION,
+            sections: sections.clone(),
+        };
+        let mut text = serde_json::to_string_pretty(&data)?;
+        text.push('\n');
+        write_atomic(&self.path, text.as_bytes())
+    }
+}
+
+fn parse(text: &str) -> Result<BTreeMap<String, LockEntry>> {
+    let data: LockData = serde_json::from_str(text)?;
+    if data.lock_version != LOCK_VERSION {
+        return Err(anyhow!(
+            "unsupported lock_version {}; this lifter understands {}",
+            data.lock_version,
+            LOCK_VERSION
+        ));
+    }
+    Ok(data.sections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(version: &str) -> LockEntry {
+        LockEntry {
+            version: version.to_string(),
+            commit: None,
+            download_url: Some(format!("https://example.com/tool-{version}.tar.gz")),
+            sha256: Some("0".repeat(64)),
+            size: Some(42),
+            updated_at: Some("2026-04-21T14:23:49Z".to_string()),
+        }
+    }
+
+    #[test]
+    fn missing_file_is_an_empty_lock() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let lock = LockFile::load(&dir.path().join("lifter.lock"))?;
+        assert_eq!(lock.get("ripgrep"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn record_round_trips_through_the_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("lifter.lock");
+        LockFile::load(&path)?.record("ripgrep", entry("14.1.1"))?;
+
+        let reloaded = LockFile::load(&path)?;
+        assert_eq!(reloaded.get("ripgrep"), Some(entry("14.1.1")));
+        Ok(())
+    }
+
+    #[test]
+    fn migrate_copies_inline_versions_without_clobbering() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("lifter.lock");
+        let lock = LockFile::load(&path)?;
+        lock.record("fzf", entry("v0.60.3"))?;
+
+        let conf = tini::Ini::from_string(
+            "[template:github_api_latest]\nversion = 1\n\n\
+             [fzf]\nversion = v0.50.0\n\n\
+             [nvim]\nversion = stable\ncommit = abc123\n\n\
+             [redbean]\npage_url = https://redbean.dev/\n\n\
+             [rg]\nplatforms = linux-x86_64, windows-x86_64\n\
+             version@linux-x86_64 = 14.1.1\n",
+        )?;
+        assert_eq!(
+            lock.migrate_from(&conf)?,
+            vec!["nvim".to_string(), "rg@linux-x86_64".to_string()]
+        );
+
+        let reloaded = LockFile::load(&path)?;
+        assert_eq!(reloaded.get("fzf"), Some(entry("v0.60.3")));
+        let nvim = reloaded.get("nvim").unwrap();
+        assert_eq!(nvim.version, "stable");
+        assert_eq!(nvim.commit.as_deref(), Some("abc123"));
+        assert_eq!(nvim.sha256, None);
+        assert_eq!(reloaded.get("redbean"), None);
+        assert_eq!(reloaded.get("rg@linux-x86_64").unwrap().version, "14.1.1");
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_lock_version_is_rejected() {
+        assert!(parse(r#"{"lock_version": 2, "sections": {}}"#).is_err());
+        assert!(parse("not json").is_err());
+    }
+}
@@ -1,11 +1,17 @@
 use anyhow::Result;
 use itertools::Itertools;
 use lifter::add::AddGithubOptions;
-use lifter::RunContext;
+use lifter::credentials::{Credentials, CREDENTIALS_PREFIX};
+use lifter::lockfile::LockFile;
+use lifter::network::{NetworkSettings, SETTINGS_SECTION};
+use lifter::ratelimit::RateLimiter;
+use lifter::templates::{self, Origin, TEMPLATE_PREFIX};
+use lifter::validators::ValidatorStore;
+use lifter::{RunContext, SectionRun};
 use log::*;
 use rayon::prelude::*;
-use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 const LONG_ABOUT: &str = "\
 Download single-file binaries from GitHub Releases (and other sites) listed in
@@ -15,7 +21,10 @@ up to date.
 Diagnostic logs are written to stderr; one CSV row per config section is
 written to stdout, with columns:
 
-    timestamp,updated,tool_name,file_name,previous_version,current_version
+    timestamp,updated,tool_name,file_name,previous_version,current_version,status
+
+status is one of updated, would_update, up_to_date, no_hit,
+ext_unsupported, checksum_mismatch, signature_invalid or error.
 
 Because stdout is pure CSV, lifter composes cleanly with awk / grep / etc.
 
@@ -34,12 +43,24 @@ EXAMPLES:
     # Crank verbosity but keep the pipe clean by sending logs to a file.
     lifter -vv 2>lifter.log | awk -F, '$2==1'
 
+    # Report outdated tools without downloading anything or touching the
+    # config, e.g. from cron.
+    lifter --dry-run | awk -F, '$7==\"would_update\"'
+
     # Run 8 downloads in parallel, restricted to a couple of sections.
     lifter -vv -x 8 -f ripgrep,fzf
 
+    # Keep installed versions in lifter.lock instead of rewriting the
+    # config. Seeds the lock from the versions already in the config;
+    # from then on it is picked up automatically.
+    lifter lock
+
     # Append a GitHub Releases definition to the active config.
     lifter add github BurntSushi/ripgrep --extract rg
 
+    # Show the templates sections can use, built-in ones included.
+    lifter templates
+
     # A GitHub token raises the API rate limit, and is effectively required
     # for configs with many github_api_latest entries.
     GITHUB_TOKEN=ghp_xxxxxxxxxxxx lifter -vv
@@ -70,12 +91,57 @@ struct Args {
     /// directory and then alongside the lifter executable.
     #[structopt(short = "c", long = "config-file")]
     configfile: Option<String>,
+    /// Lock file recording installed versions. When omitted, lifter
+    /// uses lifter.lock next to the config if it exists, and otherwise
+    /// records versions in the config itself.
+    #[structopt(parse(from_os_str), short = "l", long = "lock-file")]
+    lock_file: Option<PathBuf>,
     /// Only run these names. Comma separated.
     #[structopt(short = "f", long = "filter")]
     filter: Option<String>,
     /// Number of parallel download workers
     #[structopt(short = "x", long = "threads", default_value = "1")]
     threads: usize,
+    /// Check for newer versions and report them as would_update, but
+    /// don't download anything or modify the config or lock file
+    #[structopt(short = "n", long = "dry-run")]
+    dry_run: bool,
+    /// Longest time, in seconds, to wait for an exhausted API rate
+    /// limit to reset before failing the sections that need it
+    #[structopt(long = "max-rate-limit-wait", default_value = "600")]
+    max_rate_limit_wait: u64,
+    /// Limit in seconds for a whole request, including the download
+    /// itself (default: none). Sections can override this and the
+    /// other retry options with keys of the same name.
+    #[structopt(long = "timeout")]
+    timeout: Option<u64>,
+    /// Limit in seconds for connecting to a server [default: 30]
+    #[structopt(long = "connect-timeout")]
+    connect_timeout: Option<u64>,
+    /// Limit in seconds for a server to start responding, and then
+    /// for the rest of the response [default: 60]
+    #[structopt(long = "read-timeout")]
+    read_timeout: Option<u64>,
+    /// Tries per request, including the first [default: 5]
+    #[structopt(long = "max-attempts", parse(try_from_str = parse_max_attempts))]
+    max_attempts: Option<u32>,
+    /// Seconds before the first retry, doubling for each one after
+    /// [default: 2]
+    #[structopt(long = "retry-backoff")]
+    retry_backoff: Option<u64>,
+    /// Comma-separated statuses worth retrying
+    /// [default: 408,425,429,500,502,503,504]
+    #[structopt(long = "retry-statuses", require_delimiter = true)]
+    retry_statuses: Option<Vec<u16>>,
+    /// Proxy for all requests, e.g. http://proxy:3128, or "none" to
+    /// ignore HTTPS_PROXY and friends. Hosts in NO_PROXY are still
+    /// reached directly.
+    #[structopt(long = "proxy")]
+    proxy: Option<String>,
+    /// PEM file with the CA certificates to trust instead of the
+    /// built-in ones, e.g. for a TLS-intercepting proxy
+    #[structopt(parse(from_os_str), long = "ca-bundle")]
+    ca_bundle: Option<PathBuf>,
     #[structopt(subcommand)]
     command: Option<Command>,
 }
@@ -84,6 +150,11 @@ struct Args {
 enum Command {
     /// Add a new download definition to the active config
     Add(AddArgs),
+    /// Create or update the lock file from the versions in the config
+    Lock,
+    /// List the templates sections can use: those built into lifter and
+    /// those in the config
+    Templates,
 }
 
 #[derive(structopt::StructOpt)]
@@ -102,6 +173,10 @@ enum AddCommand {
 struct AddGithubArgs {
     /// GitHub repository in OWNER/REPO form
     repo: String,
+    /// GitHub Enterprise Server host, e.g. github.example.com, or the
+    /// URL of its API if that isn't under /api/v3
+    #[structopt(long = "host")]
+    host: Option<String>,
     /// Config section name. Defaults to the repository name.
     #[structopt(long = "name")]
     section_name: Option<String>,
@@ -158,14 +233,21 @@ fn resolve_config_path(requested: Option<&str>) -> Result<PathBuf> {
     Ok(exe_dir.unwrap_or(cwd).join("lifter.config"))
 }
 
-fn run_command(command: Command, config_path: &std::path::Path) -> Result<()> {
+fn run_command(
+    command: Command,
+    config_path: &Path,
+    lock_path: &Path,
+    ctx: &RunContext,
+) -> Result<()> {
     match command {
         Command::Add(add_args) => match add_args.command {
             AddCommand::Github(github_args) => {
                 let added = lifter::add::add_github_definition(
+                    ctx,
                     config_path,
                     &AddGithubOptions {
                         repo: github_args.repo,
+                        host: github_args.host,
                         section_name: github_args.section_name,
                         asset_filter: github_args.asset_filter,
                         extract: github_args.extract,
@@ -180,7 +262,10 @@ fn run_command(command: Command, config_path: &std::path::Path) -> Result<()> {
                         added.asset_name
                     );
                     if added.wrote_template {
-                        eprintln!("Also added missing [template:github_api_latest] template");
+                        eprintln!(
+                            "Also added missing [template:{}] template",
+                            added.template_name
+                        );
                     }
                 } else {
                     eprintln!(
@@ -191,10 +276,46 @@ fn run_command(command: Command, config_path: &std::path::Path) -> Result<()> {
                 print!("{}", added.entry);
             }
         },
+        Command::Templates => {
+            // No config is fine: there are still the built-in templates.
+            let conf = tini::Ini::from_file(config_path).unwrap_or_else(|_| tini::Ini::new());
+            for template in templates::collect(&conf) {
+                let origin = match template.origin {
+                    Origin::BuiltIn => "built in".to_string(),
+                    Origin::Config => config_path.display().to_string(),
+                    Origin::ConfigOverride => {
+                        format!("{}, replacing the built-in template", config_path.display())
+                    }
+                };
+                println!("; {}\n{}", origin, template.to_ini());
+            }
+        }
+        Command::Lock => {
+            let conf = tini::Ini::from_file(config_path)?;
+            let lock_file = LockFile::load(lock_path)?;
+            let added = lock_file.migrate_from(&conf)?;
+            for section in &added {
+                eprintln!("Recorded [{}]", section);
+            }
+            eprintln!(
+                "{} now tracks installed versions; the version and commit \
+                 lines in {} are no longer updated and can be removed",
+                lock_path.display(),
+                config_path.display()
+            );
+        }
     }
     Ok(())
 }
 
+fn parse_max_attempts(s: &str) -> Result<u32, String> {
+    match s.parse() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 #[paw::main]
 fn main(args: Args) -> Result<()> {
     // We're using threads for IO, so we can use more than cpu count
@@ -218,6 +339,26 @@ fn main(args: Args) -> Result<()> {
     // from any directory yet still update the one config that lives
     // alongside the managed binaries.
     let config_path = resolve_config_path(args.configfile.as_deref())?;
+    // The lock file lives next to the config unless told otherwise.
+    let explicit_lock = args.lock_file.is_some();
+    let lock_path = match args.lock_file {
+        Some(p) => std::env::current_dir()?.join(p),
+        None => config_path.with_file_name("lifter.lock"),
+    };
+    // Command line first, then `[lifter:settings]`, then the environment.
+    let cli_network = NetworkSettings {
+        proxy: args.proxy,
+        no_proxy: None,
+        ca_bundle: args
+            .ca_bundle
+            .map(|p| std::env::current_dir().map(|d| d.join(p)))
+            .transpose()?,
+    };
+    let settings = tini::Ini::from_file(&config_path).ok();
+    let network = match &settings {
+        Some(conf) => cli_network.or(NetworkSettings::from_config(conf, &config_path)),
+        None => cli_network,
+    };
     let working_dir = args.working_dir.unwrap_or_else(|| {
         config_path
             .parent()
@@ -233,8 +374,41 @@ fn main(args: Args) -> Result<()> {
 
     let filename = config_path.to_string_lossy().to_string();
 
+    // Shared per-run state: the HTTP agent with its retry policy,
+    // network settings and per-host credentials, one mutex guarding INI
+    // writes, one serializing CSV rows on stdout, the lock file if one
+    // is in use, the validators that make page fetches conditional, and
+    // the rate-limit budget every worker draws from. `run_section` emits its own CSV row per
+    // section (including on error) and logs errors to stderr, so the
+    // caller has nothing to do with the return value.
+    let mut ctx = RunContext::new();
+    // `0` means no limit.
+    let limit = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+    if let Some(secs) = args.timeout {
+        ctx.retry.timeout = limit(secs);
+    }
+    if let Some(secs) = args.connect_timeout {
+        ctx.retry.connect_timeout = limit(secs);
+    }
+    if let Some(secs) = args.read_timeout {
+        ctx.retry.read_timeout = limit(secs);
+    }
+    if let Some(attempts) = args.max_attempts {
+        ctx.retry.max_attempts = attempts;
+    }
+    if let Some(secs) = args.retry_backoff {
+        ctx.retry.backoff = Duration::from_secs(secs);
+    }
+    if let Some(statuses) = args.retry_statuses {
+        ctx.retry.retry_statuses = statuses;
+    }
+    ctx.set_network(&network)?;
+    if let Some(conf) = &settings {
+        ctx.credentials = Credentials::from_config(conf, &config_path)?;
+    }
+
     if let Some(command) = args.command {
-        return run_command(command, &config_path);
+        return run_command(command, &config_path, &lock_path, &ctx);
     }
 
     let conf = tini::Ini::from_file(&filename)?;
@@ -250,29 +424,17 @@ fn main(args: Args) -> Result<()> {
     // that section's values.
     //
     // Before we do anything, collect all the template sections
-    // and separate them out from the "real" sections
-
-    // This will hold the templates. The key is the name
-    // of the template and the value is another hashmap of
-    // each of the fields and field values within that template.
-    let mut templates = HashMap::new();
+    // and separate them out from the "real" sections. The templates
+    // built into lifter are available too, unless the config has
+    // its own of the same name.
+    let templates = templates::by_name(&templates::collect(&conf));
     // This will hold the "real" sections
     let mut sections = vec![];
     sections_raw.into_iter().for_each(|(name, section)| {
-        if name.starts_with("template:") {
-            // This inner map (inside a particular template)
-            // will store each of the fields and values
-            // for that template.
+        if name == SETTINGS_SECTION || name.starts_with(CREDENTIALS_PREFIX) {
+            // Read above, along with the command line options.
+        } else if name.starts_with(TEMPLATE_PREFIX) {
             debug!("Processing template: {}", name);
-            let mut inner_map = HashMap::new();
-            section.iter().for_each(|(field, value)| {
-                inner_map.insert(field.clone(), value.clone());
-            });
-
-            templates.insert(
-                name.strip_prefix("template:").unwrap().to_string(),
-                inner_map,
-            );
         } else {
             // This is not a template so move it into
             // the "real" sections list; but, only if it is not
@@ -286,15 +448,32 @@ fn main(args: Args) -> Result<()> {
     });
     trace!("Detected templates: {:?}", templates);
 
-    // Shared per-run state: one mutex guarding INI writes, one
-    // serializing CSV rows on stdout. `run_section` emits its own CSV
-    // row per section (including on error) and logs errors to stderr,
-    // so the caller has nothing to do with the return value.
-    let ctx = RunContext::new();
-
-    sections.par_iter().for_each(|(section, _hm)| {
-        lifter::run_section(section, &templates, &conf, &filename, &working_dir, &ctx);
+    if explicit_lock || lock_path.exists() {
+        debug!("Recording installed versions in {}", lock_path.display());
+        ctx.lock_file = Some(LockFile::load(&lock_path)?);
+    }
+    ctx.validators = Some(ValidatorStore::load(
+        &config_path.with_file_name(".lifter-validators.json"),
+    ));
+    ctx.rate_limit = RateLimiter::new(Duration::from_secs(args.max_rate_limit_wait));
+
+    // A section listing `platforms` runs once for each.
+    let runs = sections
+        .iter()
+        .flat_map(|(section, _hm)| SectionRun::expand(section, &templates, &conf))
+        .collect_vec();
+    runs.par_iter().for_each(|run| {
+        lifter::run_section(
+            run,
+            &templates,
+            &conf,
+            &filename,
+            &working_dir,
+            args.dry_run,
+            &ctx,
+        );
     });
+    ctx.rate_limit.log_summary();
 
     Ok(())
 }
@@ -0,0 +1,123 @@
+//! Per-run cache of fetched pages.
+//!
+//! Sections for different platforms of the same tool (`[ripgrep]` and
+//! `[ripgrep Windows]`) usually share a `page_url`. Each distinct page
+//! is fetched once per run and handed to every section that asks for
+//! it. Workers asking for a page that another worker is still fetching
+//! wait for that fetch instead of starting their own.
+//!
+//! A conditional request is keyed by its validators as well as the
+//! URL, because a `304` only means something to sections that sent
+//! the same validators. A failed fetch is not cached: the next section
+//! waiting for the page tries again itself.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use anyhow::Result;
+
+use crate::validators::PageValidators;
+
+/// A page as fetched, before any section has looked at it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Page {
+    NotModified,
+    Body {
+        body: String,
+        validators: Option<PageValidators>,
+    },
+}
+
+type Key = (String, Option<PageValidators>);
+
+#[derive(Default)]
+pub(crate) struct PageCache {
+    pages: Mutex<HashMap<Key, Arc<Mutex<Option<Page>>>>>,
+}
+
+impl PageCache {
+    /// The page at `url`, from the cache or else from `fetch`.
+    pub(crate) fn get_or_fetch(
+        &self,
+        url: &str,
+        validators: Option<&PageValidators>,
+        fetch: impl FnOnce() -> Result<Page>,
+    ) -> Result<Page> {
+        let slot = self
+            .pages
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry((url.to_string(), validators.cloned()))
+            .or_default()
+            .clone();
+        // Held for the whole fetch, so others asking for the same page
+        // queue up here.
+        let mut slot = slot.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(page) = &*slot {
+            return Ok(page.clone());
+        }
+        let page = fetch()?;
+        *slot = Some(page.clone());
+        Ok(page)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn body(text: &str) -> Page {
+        Page::Body {
+            body: text.to_string(),
+            validators: None,
+        }
+    }
+
+    #[test]
+    fn concurrent_requests_for_one_page_fetch_once() {
+        let cache = PageCache::default();
+        let fetches = AtomicUsize::new(0);
+        let url = "https://api.github.com/repos/BurntSushi/ripgrep/releases/latest";
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let page = cache
+                        .get_or_fetch(url, None, || {
+                            fetches.fetch_add(1, Ordering::SeqCst);
+                            std::thread::sleep(Duration::from_millis(50));
+                            Ok(body("{}"))
+                        })
+                        .unwrap();
+                    assert_eq!(page, body("{}"));
+                });
+            }
+        });
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn failures_are_not_cached() {
+        let cache = PageCache::default();
+        let url = "https://redbean.dev/";
+        assert!(cache
+            .get_or_fetch(url, None, || Err(anyhow!("connection reset")))
+            .is_err());
+        let page = cache.get_or_fetch(url, None, || Ok(body("<html>")));
+        assert_eq!(page.unwrap(), body("<html>"));
+    }
+
+    #[test]
+    fn conditional_and_plain_requests_are_separate() {
+        let cache = PageCache::default();
+        let url = "https://redbean.dev/";
+        let validators = PageValidators::default();
+        cache
+            .get_or_fetch(url, Some(&validators), || Ok(Page::NotModified))
+            .unwrap();
+        let page = cache.get_or_fetch(url, None, || Ok(body("<html>")));
+        assert_eq!(page.unwrap(), body("<html>"));
+    }
+}
@@ -0,0 +1,221 @@
+//! The operating system and CPU a download is for.
+//!
+//! Sections and templates can use these variables in any field:
+//!
+//! | Variable          | On 64-bit Linux              | On Windows               |
+//! |-------------------|------------------------------|--------------------------|
+//! | `{os}`            | `linux`                      | `windows`                |
+//! | `{arch}`          | `x86_64`                     | `x86_64`                 |
+//! | `{target_triple}` | `x86_64-unknown-linux-musl`  | `x86_64-pc-windows-msvc` |
+//! | `{exe_suffix}`    |                              | `.exe`                   |
+//!
+//! `{os}` and `{arch}` use Rust's names (`macos`, `aarch64`), which
+//! projects don't always follow. `os_names` and `arch_names` rename them
+//! for one section or template:
+//!
+//! ```ini
+//! [template:gotool]
+//! os_names = macos: darwin
+//! arch_names = x86_64: amd64, aarch64: arm64
+//! anchor_text = {name}_.*_{os}_{arch}.tar.gz
+//! ```
+//!
+//! A field a section sets itself, e.g. `os = ...`, is left alone.
+//!
+//! A section can also list the platforms to fetch for, rather than
+//! fetching for this machine, as `platforms = linux-x86_64,
+//! windows-x86_64`. See `SectionRun`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+
+/// Written as `<os>-<arch>`, e.g. `macos-aarch64`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Platform {
+    /// As in `std::env::consts::OS`.
+    pub os: String,
+    /// As in `std::env::consts::ARCH`.
+    pub arch: String,
+}
+
+impl Platform {
+    /// The platform lifter is running on.
+    pub fn current() -> Self {
+        Platform {
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+        }
+    }
+
+    /// `name` for this platform, as `<name>@<os>-<arch>`.
+    pub fn qualify(&self, name: &str) -> String {
+        format!("{name}@{self}")
+    }
+
+    /// Names release assets commonly use for this CPU.
+    pub(crate) fn arch_aliases(&self) -> Vec<&str> {
+        match self.arch.as_str() {
+            "x86_64" => vec!["x86_64", "amd64", "x64"],
+            "aarch64" => vec!["aarch64", "arm64"],
+            "arm" => vec!["armv7", "arm-", "arm_"],
+            "x86" => vec!["i686", "i386", "x86"],
+            other => vec![other],
+        }
+    }
+
+    /// The Rust target triple for a standalone binary on this platform.
+    /// On Linux that's the static musl build.
+    pub fn target_triple(&self) -> String {
+        let arch = match self.arch.as_str() {
+            "x86" => "i686",
+            "arm" => "armv7",
+            other => other,
+        };
+        match self.os.as_str() {
+            "linux" if arch == "armv7" => "armv7-unknown-linux-musleabihf".to_string(),
+            "linux" => format!("{arch}-unknown-linux-musl"),
+            "macos" => format!("{arch}-apple-darwin"),
+            "windows" => format!("{arch}-pc-windows-msvc"),
+            os => format!("{arch}-unknown-{os}"),
+        }
+    }
+
+    pub fn exe_suffix(&self) -> &'static str {
+        if self.os == "windows" {
+            ".exe"
+        } else {
+            ""
+        }
+    }
+
+    /// The platform variables for `section`, renamed by the
+    /// `os_names` and `arch_names` in its `values`.
+    pub(crate) fn variables(
+        &self,
+        section: &str,
+        values: &HashMap<String, String>,
+    ) -> Result<HashMap<String, String>> {
+        let rename = |key: &str, name: &str| -> Result<String> {
+            let names = values.get(key).map(String::as_str).unwrap_or_default();
+            for entry in names.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                let (from, to) = entry.split_once(':').ok_or_else(|| {
+                    anyhow!(
+                        "[{}] Invalid {} entry \"{}\", expected <name>: <alias>",
+                        section,
+                        key,
+                        entry
+                    )
+                })?;
+                if from.trim() == name {
+                    return Ok(to.trim().to_string());
+                }
+            }
+            Ok(name.to_string())
+        };
+        Ok(HashMap::from([
+            ("os".to_string(), rename("os_names", &self.os)?),
+            ("arch".to_string(), rename("arch_names", &self.arch)?),
+            ("target_triple".to_string(), self.target_triple()),
+            ("exe_suffix".to_string(), self.exe_suffix().to_string()),
+        ]))
+    }
+}
+
+impl Default for Platform {
+    fn default() -> Self {
+        Self::current()
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.os, self.arch)
+    }
+}
+
+impl FromStr for Platform {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().split_once('-') {
+            Some((os, arch)) if !os.is_empty() && !arch.is_empty() => Ok(Platform {
+                os: os.to_string(),
+                arch: arch.to_string(),
+            }),
+            _ => Err(anyhow!(
+                "Invalid platform \"{}\", expected <os>-<arch> like linux-x86_64",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn platform(os: &str, arch: &str) -> Platform {
+        Platform {
+            os: os.to_string(),
+            arch: arch.to_string(),
+        }
+    }
+
+    #[test]
+    fn target_triples_follow_rust_names() {
+        for (os, arch, triple) in [
+            ("linux", "x86_64", "x86_64-unknown-linux-musl"),
+            ("linux", "arm", "armv7-unknown-linux-musleabihf"),
+            ("macos", "aarch64", "aarch64-apple-darwin"),
+            ("windows", "x86", "i686-pc-windows-msvc"),
+            ("freebsd", "x86_64", "x86_64-unknown-freebsd"),
+        ] {
+            assert_eq!(platform(os, arch).target_triple(), triple);
+        }
+        assert_eq!(platform("windows", "x86_64").exe_suffix(), ".exe");
+        assert_eq!(platform("macos", "x86_64").exe_suffix(), "");
+    }
+
+    #[test]
+    fn platforms_are_written_os_dash_arch() -> Result<()> {
+        let parsed: Platform = " windows-x86_64 ".parse()?;
+        assert_eq!(parsed, platform("windows", "x86_64"));
+        assert_eq!(parsed.qualify("version"), "version@windows-x86_64");
+        for bad in ["linux", "-x86_64", "linux-"] {
+            assert!(bad.parse::<Platform>().is_err(), "{bad}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn variables_use_the_section_names_for_os_and_arch() -> Result<()> {
+        let values = HashMap::from([
+            ("os_names".to_string(), "macos: darwin".to_string()),
+            (
+                "arch_names".to_string(),
+                "x86_64: amd64, aarch64: arm64".to_string(),
+            ),
+        ]);
+        let vars = platform("macos", "aarch64").variables("gotool", &values)?;
+        assert_eq!(vars["os"], "darwin");
+        assert_eq!(vars["arch"], "arm64");
+        assert_eq!(vars["target_triple"], "aarch64-apple-darwin");
+
+        let vars = platform("linux", "x86").variables("gotool", &values)?;
+        assert_eq!(
+            (vars["os"].as_str(), vars["arch"].as_str()),
+            ("linux", "x86")
+        );
+
+        let bad = HashMap::from([("arch_names".to_string(), "amd64".to_string())]);
+        let err = platform("linux", "x86_64")
+            .variables("gotool", &bad)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("[gotool] Invalid arch_names"), "{err}");
+        Ok(())
+    }
+}
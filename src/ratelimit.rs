@@ -0,0 +1,230 @@
+//! Rate-limit tracking shared by all sections of a run.
+//!
+//! GitHub's API says how much of the hourly budget is left on every
+//! response:
+//!
+//! ```text
+//! X-RateLimit-Limit: 60
+//! X-RateLimit-Remaining: 0
+//! X-RateLimit-Reset: 1776781429
+//! ```
+//!
+//! and answers an over-budget request with `403` or `429`, sometimes
+//! with a `Retry-After: <seconds>`. Rather than each worker guessing
+//! with its own backoff, every response is fed to one tracker. Once a
+//! host's budget is exhausted, every worker about to ask that host
+//! waits until the reset time, or fails straight away if that is
+//! further off than the configured cap. Other hosts are unaffected.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use log::*;
+use ureq::http::HeaderMap;
+use url::Url;
+
+use crate::reporter::format_rfc3339_utc;
+
+/// How long to wait for a budget to reset unless told otherwise.
+pub const DEFAULT_MAX_WAIT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Default)]
+struct HostState {
+    limit: Option<u64>,
+    remaining: Option<u64>,
+    /// Seconds since the epoch.
+    reset: Option<u64>,
+    paused_until: Option<SystemTime>,
+}
+
+pub struct RateLimiter {
+    max_wait: Duration,
+    hosts: Mutex<BTreeMap<String, HostState>>,
+}
+
+impl RateLimiter {
+    /// A tracker that fails rather than wait longer than `max_wait`
+    /// for a budget to reset.
+    pub fn new(max_wait: Duration) -> Self {
+        RateLimiter {
+            max_wait,
+            hosts: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Block until the host of `url` may be asked again.
+    pub(crate) fn wait(&self, section: &str, url: &str) -> Result<()> {
+        let host = host_of(url);
+        loop {
+            let paused_until = self
+                .hosts
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get(&host)
+                .and_then(|h| h.paused_until);
+            let Some(Ok(wait)) = paused_until.map(|t| t.duration_since(SystemTime::now())) else {
+                return Ok(());
+            };
+            if wait > self.max_wait {
+                return Err(anyhow!(
+                    "[{}] Rate limit for {} is exhausted and resets in {}s, \
+                     longer than the {}s allowed by --max-rate-limit-wait",
+                    section,
+                    host,
+                    wait.as_secs(),
+                    self.max_wait.as_secs()
+                ));
+            }
+            info!(
+                "[{}] Rate limit for {} is exhausted. Waiting {}s for it to reset...",
+                section,
+                host,
+                wait.as_secs()
+            );
+            std::thread::sleep(wait);
+            // Loop: another response may have pushed the reset back.
+        }
+    }
+
+    /// Take note of a response from `url`. Returns `true` if it was
+    /// refused for being over the rate limit, in which case the request
+    /// should be retried after `wait`.
+    pub(crate) fn observe(&self, url: &str, status: u16, headers: &HeaderMap) -> bool {
+        let number =
+            |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.trim().parse().ok() };
+        let limit = number("X-RateLimit-Limit");
+        let remaining = number("X-RateLimit-Remaining");
+        let reset = number("X-RateLimit-Reset");
+        // Only the delay-seconds form; GitHub doesn't send HTTP dates.
+        let retry_after = number("Retry-After").map(Duration::from_secs);
+
+        let now = SystemTime::now();
+        let pause = match (retry_after, remaining, reset) {
+            (Some(after), _, _) => Some(now + after),
+            // One second of slack for clock skew.
+            (None, Some(0), Some(reset)) => Some(UNIX_EPOCH + Duration::from_secs(reset + 1)),
+            _ => None,
+        };
+
+        let mut hosts = self.hosts.lock().unwrap_or_else(PoisonError::into_inner);
+        let state = hosts.entry(host_of(url)).or_default();
+        if remaining.is_some() {
+            state.limit = limit;
+            state.remaining = remaining;
+            state.reset = reset;
+        }
+        if let Some(pause) = pause {
+            state.paused_until = state.paused_until.max(Some(pause));
+        }
+        matches!(status, 403 | 429) && pause.is_some()
+    }
+
+    /// Log how much of each host's budget is left, more loudly when
+    /// it's nearly gone.
+    pub fn log_summary(&self) {
+        let hosts = self.hosts.lock().unwrap_or_else(PoisonError::into_inner);
+        for (host, state) in hosts.iter() {
+            let (Some(limit), Some(remaining)) = (state.limit, state.remaining) else {
+                continue;
+            };
+            let resets = state
+                .reset
+                .map(|r| format!(", resets at {}", format_rfc3339_utc(r as i64)))
+                .unwrap_or_default();
+            let msg =
+                format!("Rate limit for {host}: {remaining} of {limit} requests left{resets}");
+            if remaining * 10 < limit {
+                warn!("{}", msg);
+            } else {
+                info!("{}", msg);
+            }
+        }
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_WAIT)
+    }
+}
+
+pub(crate) fn host_of(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(String::from))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const API: &str = "https://api.github.com/repos/BurntSushi/ripgrep/releases/latest";
+
+    fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, value.parse().unwrap());
+        }
+        map
+    }
+
+    fn epoch_secs_from_now(secs: u64) -> String {
+        (SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + secs)
+            .to_string()
+    }
+
+    #[test]
+    fn exhausted_budget_beyond_the_cap_fails_fast() {
+        let limiter = RateLimiter::new(Duration::from_secs(60));
+        let h = headers(&[
+            ("X-RateLimit-Limit", "60".into()),
+            ("X-RateLimit-Remaining", "0".into()),
+            ("X-RateLimit-Reset", epoch_secs_from_now(3600)),
+        ]);
+        assert!(limiter.observe(API, 403, &h));
+        let err = limiter.wait("ripgrep", API).unwrap_err().to_string();
+        assert!(err.contains("api.github.com"), "{err}");
+        assert!(err.contains("--max-rate-limit-wait"), "{err}");
+        // Other hosts carry on.
+        assert!(limiter.wait("redbean", "https://redbean.dev/").is_ok());
+    }
+
+    #[test]
+    fn retry_after_pauses_until_it_elapses() {
+        let limiter = RateLimiter::new(Duration::from_secs(60));
+        assert!(limiter.observe(API, 429, &headers(&[("Retry-After", "1".into())])));
+        let start = std::time::Instant::now();
+        limiter.wait("ripgrep", API).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(900));
+    }
+
+    #[test]
+    fn forbidden_without_rate_limit_headers_is_not_a_rate_limit() {
+        let limiter = RateLimiter::default();
+        let h = headers(&[
+            ("X-RateLimit-Remaining", "59".into()),
+            ("X-RateLimit-Reset", epoch_secs_from_now(3600)),
+        ]);
+        assert!(!limiter.observe(API, 403, &h));
+        assert!(!limiter.observe(API, 403, &HeaderMap::new()));
+        assert!(limiter.wait("ripgrep", API).is_ok());
+    }
+
+    #[test]
+    fn past_reset_does_not_wait() {
+        let limiter = RateLimiter::new(Duration::ZERO);
+        let h = headers(&[
+            ("X-RateLimit-Remaining", "0".into()),
+            ("X-RateLimit-Reset", "1000".into()),
+        ]);
+        limiter.observe(API, 200, &h);
+        assert!(limiter.wait("ripgrep", API).is_ok());
+    }
+}
@@ -2,13 +2,15 @@
 //!
 //! Each section of the config produces exactly one CSV row on stdout, no
 //! header, regardless of outcome. Logging (on stderr) is for humans;
-//! this channel is for tooling. The six columns are:
+//! this channel is for tooling. The seven columns are:
 //!
 //! ```text
-//! timestamp,updated,tool_name,file_name,previous_version,current_version
+//! timestamp,updated,tool_name,file_name,previous_version,current_version,status
 //! ```
 //!
 //! `timestamp` is UTC RFC 3339, second precision (`YYYY-MM-DDTHH:MM:SSZ`).
+//! `status` is one of the `Status` strings; it comes last so consumers
+//! indexing the earlier columns are unaffected.
 //! Rows are serialized atomically under a mutex so parallel sections
 //! never interleave. Flushed per row so a consumer piping to
 //! `grep`/`awk` sees each section's result as soon as it finishes.
@@ -17,6 +19,35 @@ use std::io::{self, Stdout, Write};
 use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Why a section did or didn't update. Rendered as the `status` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Updated,
+    /// `--dry-run` found a newer version but didn't download it.
+    WouldUpdate,
+    UpToDate,
+    NoHit,
+    ExtUnsupported,
+    ChecksumMismatch,
+    SignatureInvalid,
+    Error,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Updated => "updated",
+            Status::WouldUpdate => "would_update",
+            Status::UpToDate => "up_to_date",
+            Status::NoHit => "no_hit",
+            Status::ExtUnsupported => "ext_unsupported",
+            Status::ChecksumMismatch => "checksum_mismatch",
+            Status::SignatureInvalid => "signature_invalid",
+            Status::Error => "error",
+        }
+    }
+}
+
 /// One outcome for one section of the config.
 ///
 /// `file_name` and `previous_version` are `Option` because we may not
@@ -29,6 +60,7 @@ pub struct OutputRecord {
     pub file_name: Option<String>,
     pub previous_version: Option<String>,
     pub current_version: Option<String>,
+    pub status: Status,
 }
 
 /// Serializes `OutputRecord`s as CSV rows to stdout, one row per call,
@@ -80,6 +112,8 @@ fn format_row(timestamp: &str, r: &OutputRecord) -> String {
     append_field(&mut buf, r.previous_version.as_deref().unwrap_or(""));
     buf.push(',');
     append_field(&mut buf, r.current_version.as_deref().unwrap_or(""));
+    buf.push(',');
+    append_field(&mut buf, r.status.as_str());
     buf.push('\n');
     buf
 }
@@ -87,7 +121,7 @@ fn format_row(timestamp: &str, r: &OutputRecord) -> String {
 /// Current wall-clock time as RFC 3339 UTC, second precision.
 /// Falls back to the epoch if the system clock is somehow before
 /// 1970-01-01 — not worth propagating an error for row output.
-fn now_rfc3339_utc() -> String {
+pub(crate) fn now_rfc3339_utc() -> String {
     let secs = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map(|d| d.as_secs() as i64)
@@ -100,7 +134,7 @@ fn now_rfc3339_utc() -> String {
 /// The civil-date arithmetic is Howard Hinnant's `civil_from_days`
 /// (public domain), which is branch-light, valid for any year in
 /// `i32`, and doesn't need a table or an external crate.
-fn format_rfc3339_utc(secs_since_epoch: i64) -> String {
+pub(crate) fn format_rfc3339_utc(secs_since_epoch: i64) -> String {
     let days = secs_since_epoch.div_euclid(86_400);
     let tod = secs_since_epoch.rem_euclid(86_400);
     let (y, m, d) = civil_from_days(days);
@@ -162,6 +196,11 @@ mod tests {
             file_name: file.map(String::from),
             previous_version: prev.map(String::from),
             current_version: curr.map(String::from),
+            status: if updated {
+                Status::Updated
+            } else {
+                Status::UpToDate
+            },
         }
     }
 
@@ -170,7 +209,7 @@ mod tests {
         let r = rec(true, "ripgrep", Some("rg"), Some("13.0.0"), Some("14.1.0"));
         assert_eq!(
             format_row(TS, &r),
-            "2026-04-21T12:00:00Z,1,ripgrep,rg,13.0.0,14.1.0\n"
+            "2026-04-21T12:00:00Z,1,ripgrep,rg,13.0.0,14.1.0,updated\n"
         );
     }
 
@@ -179,14 +218,18 @@ mod tests {
         let r = rec(false, "ripgrep", Some("rg"), Some("14.1.0"), Some("14.1.0"));
         assert_eq!(
             format_row(TS, &r),
-            "2026-04-21T12:00:00Z,0,ripgrep,rg,14.1.0,14.1.0\n"
+            "2026-04-21T12:00:00Z,0,ripgrep,rg,14.1.0,14.1.0,up_to_date\n"
         );
     }
 
     #[test]
     fn blank_optionals_left_empty() {
-        let r = rec(false, "broken", None, Some("1.0"), None);
-        assert_eq!(format_row(TS, &r), "2026-04-21T12:00:00Z,0,broken,,1.0,\n");
+        let mut r = rec(false, "broken", None, Some("1.0"), None);
+        r.status = Status::Error;
+        assert_eq!(
+            format_row(TS, &r),
+            "2026-04-21T12:00:00Z,0,broken,,1.0,,error\n"
+        );
     }
 
     #[test]
@@ -201,7 +244,7 @@ mod tests {
         );
         assert_eq!(
             format_row(TS, &r),
-            "2026-04-21T12:00:00Z,1,ripgrep Windows,rg.exe,13.0.0,14.1.0\n"
+            "2026-04-21T12:00:00Z,1,ripgrep Windows,rg.exe,13.0.0,14.1.0,updated\n"
         );
     }
 
@@ -210,7 +253,7 @@ mod tests {
         let r = rec(true, "weird,tool", Some("x"), Some("1"), Some("2"));
         assert_eq!(
             format_row(TS, &r),
-            "2026-04-21T12:00:00Z,1,\"weird,tool\",x,1,2\n"
+            "2026-04-21T12:00:00Z,1,\"weird,tool\",x,1,2,updated\n"
         );
     }
 
@@ -219,7 +262,7 @@ mod tests {
         let r = rec(true, r#"with"quote"#, Some("x"), Some("1"), Some("2"));
         assert_eq!(
             format_row(TS, &r),
-            "2026-04-21T12:00:00Z,1,\"with\"\"quote\",x,1,2\n"
+            "2026-04-21T12:00:00Z,1,\"with\"\"quote\",x,1,2,updated\n"
         );
     }
 
@@ -228,7 +271,27 @@ mod tests {
         let r = rec(true, "multi\nline", Some("x"), Some("1"), Some("2"));
         assert_eq!(
             format_row(TS, &r),
-            "2026-04-21T12:00:00Z,1,\"multi\nline\",x,1,2\n"
+            "2026-04-21T12:00:00Z,1,\"multi\nline\",x,1,2,updated\n"
+        );
+    }
+
+    #[test]
+    fn checksum_mismatch_row() {
+        let mut r = rec(false, "ripgrep", Some("rg"), Some("13.0.0"), Some("14.1.0"));
+        r.status = Status::ChecksumMismatch;
+        assert_eq!(
+            format_row(TS, &r),
+            "2026-04-21T12:00:00Z,0,ripgrep,rg,13.0.0,14.1.0,checksum_mismatch\n"
+        );
+    }
+
+    #[test]
+    fn would_update_row_is_not_marked_updated() {
+        let mut r = rec(false, "ripgrep", Some("rg"), Some("13.0.0"), Some("14.1.0"));
+        r.status = Status::WouldUpdate;
+        assert_eq!(
+            format_row(TS, &r),
+            "2026-04-21T12:00:00Z,0,ripgrep,rg,13.0.0,14.1.0,would_update\n"
         );
     }
 
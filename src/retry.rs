@@ -0,0 +1,317 @@
+//! Timeouts and retries for every HTTP request lifter makes.
+//!
+//! One `RetryPolicy` covers page and API fetches, checksum and signature
+//! files, artifact downloads and `lifter add`. The defaults can be
+//! changed for the whole run on the command line and per section in the
+//! config:
+//!
+//! ```ini
+//! [big-tool]
+//! timeout = 600          ; whole request, including the body
+//! connect_timeout = 10   ; DNS, TCP and TLS handshake
+//! read_timeout = 30      ; waiting for the headers, then for the body
+//! max_attempts = 8
+//! retry_backoff = 5      ; delay before the first retry
+//! retry_statuses = 429, 500, 502, 503, 504
+//! ```
+//!
+//! Durations are in seconds; `0` means no limit. A failed attempt is
+//! retried if it never got a response (connection refused, timeout,
+//! DNS failure, cut off mid-body) or got one of `retry_statuses`. The
+//! delay doubles on each retry, up to a minute, and is jittered so
+//! parallel workers don't retry in lockstep. Responses refused for
+//! being over a rate limit are retried without backoff, because the
+//! rate limiter already waits for the reset (see `ratelimit`).
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use log::*;
+use ureq::typestate::WithoutBody;
+use ureq::RequestBuilder;
+
+/// Longest delay between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// https://developer.mozilla.org/en-US/docs/Web/HTTP/Status#client_error_responses
+const DEFAULT_RETRY_STATUSES: [u16; 7] = [408, 425, 429, 500, 502, 503, 504];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Limit for a whole request, body included.
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    /// Limit for the response headers to arrive, and then for the body,
+    /// so a server that stops sending partway doesn't hang the run.
+    pub read_timeout: Option<Duration>,
+    /// Tries in total, including the first.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub backoff: Duration,
+    pub retry_statuses: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            timeout: None,
+            connect_timeout: Some(Duration::from_secs(30)),
+            read_timeout: Some(Duration::from_secs(60)),
+            max_attempts: 5,
+            backoff: Duration::from_secs(2),
+            retry_statuses: DEFAULT_RETRY_STATUSES.to_vec(),
+        }
+    }
+}
+
+/// What became of one attempt.
+pub(crate) enum Attempt<T> {
+    Done(T),
+    /// Try again after the backoff delay, for the given reason.
+    Retry(String),
+    /// Try again straight away.
+    RetryNow(String),
+}
+
+impl RetryPolicy {
+    /// This policy with any of the settings in `values`, a section of
+    /// the config, applied on top.
+    pub fn with_overrides(&self, section: &str, values: &HashMap<String, String>) -> Result<Self> {
+        let mut policy = self.clone();
+        let invalid =
+            |key: &str, value: &str| anyhow!("[{}] Invalid {} \"{}\"", section, key, value);
+        let seconds = |key: &str| -> Result<Option<Option<Duration>>> {
+            let Some(value) = values.get(key) else {
+                return Ok(None);
+            };
+            let secs: f64 = value.trim().parse().map_err(|_| invalid(key, value))?;
+            if !(secs.is_finite() && secs >= 0.0) {
+                return Err(invalid(key, value));
+            }
+            Ok(Some((secs > 0.0).then(|| Duration::from_secs_f64(secs))))
+        };
+        if let Some(t) = seconds("timeout")? {
+            policy.timeout = t;
+        }
+        if let Some(t) = seconds("connect_timeout")? {
+            policy.connect_timeout = t;
+        }
+        if let Some(t) = seconds("read_timeout")? {
+            policy.read_timeout = t;
+        }
+        if let Some(t) = seconds("retry_backoff")? {
+            policy.backoff = t.unwrap_or_default();
+        }
+        if let Some(value) = values.get("max_attempts") {
+            policy.max_attempts = value
+                .trim()
+                .parse()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| invalid("max_attempts", value))?;
+        }
+        if let Some(value) = values.get("retry_statuses") {
+            policy.retry_statuses = value
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse().map_err(|_| invalid("retry_statuses", value)))
+                .collect::<Result<_>>()?;
+        }
+        Ok(policy)
+    }
+
+    /// Set this policy's timeouts on `request`.
+    pub(crate) fn apply(
+        &self,
+        request: RequestBuilder<WithoutBody>,
+    ) -> RequestBuilder<WithoutBody> {
+        request
+            .config()
+            .timeout_global(self.timeout)
+            .timeout_connect(self.connect_timeout)
+            .timeout_recv_response(self.read_timeout)
+            .timeout_recv_body(self.read_timeout)
+            .build()
+    }
+
+    pub(crate) fn retries_status(&self, status: u16) -> bool {
+        self.retry_statuses.contains(&status)
+    }
+
+    /// An attempt that failed without a usable response: retry if the
+    /// failure might be transient.
+    pub(crate) fn transport_error<T>(&self, url: &str, e: ureq::Error) -> Result<Attempt<T>> {
+        match e {
+            ureq::Error::Io(_)
+            | ureq::Error::Timeout(_)
+            | ureq::Error::ConnectionFailed
+            | ureq::Error::HostNotFound => Ok(Attempt::Retry(e.to_string())),
+            _ => Err(anyhow!("Unexpected error fetching {}: {}", url, e)),
+        }
+    }
+
+    /// Call `attempt` until it's `Done`, fails, or runs out of tries.
+    pub(crate) fn run<T>(
+        &self,
+        section: &str,
+        url: &str,
+        mut attempt: impl FnMut() -> Result<Attempt<T>>,
+    ) -> Result<T> {
+        let mut tries = 0;
+        loop {
+            tries += 1;
+            let (reason, wait) = match attempt()? {
+                Attempt::Done(value) => return Ok(value),
+                Attempt::Retry(reason) => (reason, true),
+                Attempt::RetryNow(reason) => (reason, false),
+            };
+            if tries >= self.max_attempts {
+                return Err(anyhow!(
+                    "[{}] Giving up on {} after {} attempts: {}",
+                    section,
+                    url,
+                    tries,
+                    reason
+                ));
+            }
+            if wait {
+                let delay = self.delay(tries);
+                info!(
+                    "[{}] {} fetching {}. Retrying in {:.1} secs...",
+                    section,
+                    reason,
+                    url,
+                    delay.as_secs_f64()
+                );
+                std::thread::sleep(delay);
+            } else {
+                debug!("[{}] {} fetching {}. Retrying...", section, reason, url);
+            }
+        }
+    }
+
+    /// Delay after the `tries`th failed attempt: exponential, capped,
+    /// and jittered to somewhere in its upper half.
+    fn delay(&self, tries: u32) -> Duration {
+        let full = self
+            .backoff
+            .saturating_mul(1 << (tries - 1).min(16))
+            .min(MAX_BACKOFF.max(self.backoff));
+        let half = full / 2;
+        // `RandomState` is randomly seeded: good enough for jitter.
+        let random = RandomState::new().build_hasher().finish();
+        half + half.mul_f64((random % 1000) as f64 / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map<const N: usize>(pairs: [(&str, &str); N]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn overrides_apply_on_top_of_the_defaults() -> Result<()> {
+        let policy = RetryPolicy::default().with_overrides(
+            "ripgrep",
+            &map([
+                ("timeout", "600"),
+                ("connect_timeout", "0"),
+                ("max_attempts", "8"),
+                ("retry_backoff", "0.5"),
+                ("retry_statuses", "429, 503"),
+            ]),
+        )?;
+        assert_eq!(
+            policy,
+            RetryPolicy {
+                timeout: Some(Duration::from_secs(600)),
+                connect_timeout: None,
+                read_timeout: Some(Duration::from_secs(60)),
+                max_attempts: 8,
+                backoff: Duration::from_millis(500),
+                retry_statuses: vec![429, 503],
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_values_name_the_section_and_key() {
+        for (key, value) in [
+            ("timeout", "soon"),
+            ("read_timeout", "-1"),
+            ("max_attempts", "0"),
+            ("retry_statuses", "429,5xx"),
+        ] {
+            let err = RetryPolicy::default()
+                .with_overrides("ripgrep", &map([(key, value)]))
+                .unwrap_err()
+                .to_string();
+            assert!(err.starts_with("[ripgrep]") && err.contains(key), "{err}");
+        }
+    }
+
+    #[test]
+    fn delay_doubles_with_jitter_up_to_the_cap() {
+        let policy = RetryPolicy {
+            backoff: Duration::from_secs(2),
+            ..Default::default()
+        };
+        for tries in 1..=10 {
+            let full = (Duration::from_secs(2) * 2u32.pow(tries - 1)).min(MAX_BACKOFF);
+            let delay = policy.delay(tries);
+            assert!(delay >= full / 2 && delay <= full, "{tries}: {delay:?}");
+        }
+    }
+
+    #[test]
+    fn run_gives_up_after_max_attempts() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut calls = 0;
+        let err = policy
+            .run(
+                "ripgrep",
+                "https://example.com/",
+                || -> Result<Attempt<()>> {
+                    calls += 1;
+                    Ok(Attempt::Retry("Got status 503".to_string()))
+                },
+            )
+            .unwrap_err();
+        assert_eq!(calls, 3);
+        assert!(err.to_string().contains("after 3 attempts: Got status 503"));
+    }
+
+    #[test]
+    fn run_returns_the_first_success() -> Result<()> {
+        let policy = RetryPolicy {
+            backoff: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut calls = 0;
+        let value = policy.run("ripgrep", "https://example.com/", || {
+            calls += 1;
+            Ok(if calls < 3 {
+                Attempt::RetryNow("rate limited".to_string())
+            } else {
+                Attempt::Done(calls)
+            })
+        })?;
+        assert_eq!(value, 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,376 @@
+//! Detached-signature verification for downloaded artifacts.
+//!
+//! Two signature formats are understood, both Ed25519 underneath:
+//!
+//!   * minisign — the public key is the base64 line from a
+//!     `minisign.pub` file (`RW...`), and the signature is a
+//!     `.minisig` file. Both the prehashed (`ED`, BLAKE2b-512) and the
+//!     legacy (`Ed`) algorithms are accepted, and the trusted comment's
+//!     global signature is checked too.
+//!   * OpenSSH — the public key is an `ssh-ed25519 AAAA...` line, and
+//!     the signature is the armored output of
+//!     `ssh-keygen -Y sign -f key -n <namespace> file`.
+//!
+//! Keys live in the config, so verification never needs the network
+//! beyond fetching the signature itself.
+
+use std::io::Read;
+
+use anyhow::{anyhow, bail, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use blake2::Blake2b512;
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256, Sha512};
+
+/// A public key that artifacts of one section must be signed with.
+#[derive(Debug)]
+pub(crate) enum PublicKey {
+    Minisign {
+        key_id: [u8; 8],
+        key: VerifyingKey,
+    },
+    Ssh {
+        key: VerifyingKey,
+        namespace: String,
+    },
+}
+
+impl PublicKey {
+    /// Parse a minisign public key. Accepts either the bare base64 line
+    /// or the whole two-line `minisign.pub` file.
+    pub(crate) fn minisign(encoded: &str) -> Result<PublicKey> {
+        let line =
+            last_non_comment_line(encoded).ok_or_else(|| anyhow!("minisign_pubkey is empty"))?;
+        let raw = BASE64
+            .decode(line)
+            .map_err(|e| anyhow!("minisign_pubkey is not valid base64: {}", e))?;
+        if raw.len() != 42 || &raw[..2] != b"Ed" {
+            bail!("minisign_pubkey is not an Ed25519 minisign public key");
+        }
+        let key_id = raw[2..10].try_into()?;
+        let key = VerifyingKey::from_bytes(raw[10..].try_into()?)?;
+        Ok(PublicKey::Minisign { key_id, key })
+    }
+
+    /// Parse an OpenSSH `ssh-ed25519 AAAA... [comment]` public key.
+    /// `namespace` must match the `-n` given to `ssh-keygen -Y sign`.
+    pub(crate) fn ssh(line: &str, namespace: &str) -> Result<PublicKey> {
+        let mut parts = line.split_whitespace();
+        let (Some("ssh-ed25519"), Some(blob)) = (parts.next(), parts.next()) else {
+            bail!("ssh_pubkey must be an \"ssh-ed25519 AAAA...\" public key");
+        };
+        let blob = BASE64
+            .decode(blob)
+            .map_err(|e| anyhow!("ssh_pubkey is not valid base64: {}", e))?;
+        Ok(PublicKey::Ssh {
+            key: parse_ssh_ed25519_key(&blob)?,
+            namespace: namespace.to_string(),
+        })
+    }
+
+    /// Suffix of the signature file conventionally published next to
+    /// each artifact, used when no `signature_anchor_text` is given.
+    pub(crate) fn signature_suffix(&self) -> &'static str {
+        match self {
+            PublicKey::Minisign { .. } => ".minisig",
+            PublicKey::Ssh { .. } => ".sig",
+        }
+    }
+
+    /// Check `signature` (the text of the detached signature file)
+    /// over the bytes read from `artifact`. Any error means "do not
+    /// install".
+    pub(crate) fn verify(&self, artifact: impl Read, signature: &str) -> Result<()> {
+        match self {
+            PublicKey::Minisign { key_id, key } => {
+                verify_minisign(key_id, key, artifact, signature)
+            }
+            PublicKey::Ssh { key, namespace } => verify_sshsig(key, namespace, artifact, signature),
+        }
+    }
+}
+
+fn last_non_comment_line(text: &str) -> Option<&str> {
+    text.lines()
+        .map(str::trim)
+        .rfind(|l| !l.is_empty() && !l.starts_with("untrusted comment:"))
+}
+
+/// Hash everything `reader` yields with `D`.
+fn digest_reader<D: Digest + std::io::Write>(mut reader: impl Read) -> Result<Vec<u8>> {
+    let mut hasher = D::new();
+    std::io::copy(&mut reader, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+fn verify_minisign(
+    key_id: &[u8; 8],
+    key: &VerifyingKey,
+    mut artifact: impl Read,
+    signature: &str,
+) -> Result<()> {
+    let mut lines = signature
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.starts_with("untrusted comment:") && !l.is_empty());
+    let (Some(sig_line), Some(trusted), Some(global_line)) =
+        (lines.next(), lines.next(), lines.next())
+    else {
+        bail!("malformed minisign signature file");
+    };
+    let trusted_comment = trusted
+        .strip_prefix("trusted comment: ")
+        .ok_or_else(|| anyhow!("minisign signature has no trusted comment"))?;
+
+    let raw = BASE64.decode(sig_line)?;
+    if raw.len() != 74 {
+        bail!("malformed minisign signature");
+    }
+    if &raw[2..10] != key_id {
+        bail!("minisign signature was made with a different key");
+    }
+    let sig_bytes: [u8; 64] = raw[10..].try_into()?;
+    let sig = Signature::from_bytes(&sig_bytes);
+    match &raw[..2] {
+        b"ED" => key.verify_strict(&digest_reader::<Blake2b512>(artifact)?, &sig)?,
+        b"Ed" => {
+            // The legacy algorithm signs the artifact itself, so it has
+            // to be held in memory.
+            let mut bytes = Vec::new();
+            artifact.read_to_end(&mut bytes)?;
+            key.verify_strict(&bytes, &sig)?
+        }
+        _ => bail!("unsupported minisign signature algorithm"),
+    }
+
+    let global: [u8; 64] = BASE64.decode(global_line)?[..]
+        .try_into()
+        .map_err(|_| anyhow!("malformed minisign global signature"))?;
+    let mut signed_comment = sig_bytes.to_vec();
+    signed_comment.extend_from_slice(trusted_comment.as_bytes());
+    key.verify_strict(&signed_comment, &Signature::from_bytes(&global))
+        .map_err(|_| anyhow!("minisign trusted comment signature is invalid"))
+}
+
+/// Cursor over the SSH wire encoding (RFC 4251 `string`s and `uint32`s).
+struct SshReader<'a>(&'a [u8]);
+
+impl<'a> SshReader<'a> {
+    fn u32(&mut self) -> Result<u32> {
+        if self.0.len() < 4 {
+            bail!("truncated SSH data");
+        }
+        let (head, rest) = self.0.split_at(4);
+        self.0 = rest;
+        Ok(u32::from_be_bytes(head.try_into()?))
+    }
+
+    fn string(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        if self.0.len() < len {
+            bail!("truncated SSH data");
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+}
+
+fn ssh_string(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(data);
+}
+
+fn parse_ssh_ed25519_key(blob: &[u8]) -> Result<VerifyingKey> {
+    let mut r = SshReader(blob);
+    if r.string()? != b"ssh-ed25519" {
+        bail!("only ssh-ed25519 keys are supported");
+    }
+    Ok(VerifyingKey::from_bytes(r.string()?.try_into()?)?)
+}
+
+const SSHSIG_MAGIC: &[u8] = b"SSHSIG";
+
+fn verify_sshsig(
+    key: &VerifyingKey,
+    namespace: &str,
+    artifact: impl Read,
+    signature: &str,
+) -> Result<()> {
+    let armored: String = signature
+        .lines()
+        .map(str::trim)
+        .skip_while(|l| *l != "-----BEGIN SSH SIGNATURE-----")
+        .skip(1)
+        .take_while(|l| *l != "-----END SSH SIGNATURE-----")
+        .collect();
+    let blob = BASE64
+        .decode(armored)
+        .map_err(|e| anyhow!("malformed SSH signature: {}", e))?;
+
+    let body = blob
+        .strip_prefix(SSHSIG_MAGIC)
+        .ok_or_else(|| anyhow!("not an SSH signature"))?;
+    let mut r = SshReader(body);
+    if r.u32()? != 1 {
+        bail!("unsupported SSH signature version");
+    }
+    if parse_ssh_ed25519_key(r.string()?)? != *key {
+        bail!("SSH signature was made with a different key");
+    }
+    let sig_namespace = r.string()?;
+    if sig_namespace != namespace.as_bytes() {
+        bail!(
+            "SSH signature namespace is {:?}, expected {:?}",
+            String::from_utf8_lossy(sig_namespace),
+            namespace
+        );
+    }
+    let reserved = r.string()?;
+    let hash_algorithm = r.string()?;
+    let mut sig_blob = SshReader(r.string()?);
+    if sig_blob.string()? != b"ssh-ed25519" {
+        bail!("only ssh-ed25519 signatures are supported");
+    }
+    let sig = Signature::from_bytes(sig_blob.string()?.try_into()?);
+
+    let hashed = match hash_algorithm {
+        b"sha512" => digest_reader::<Sha512>(artifact)?,
+        b"sha256" => digest_reader::<Sha256>(artifact)?,
+        other => bail!(
+            "unsupported SSH signature hash {:?}",
+            String::from_utf8_lossy(other)
+        ),
+    };
+    let mut signed = SSHSIG_MAGIC.to_vec();
+    ssh_string(&mut signed, namespace.as_bytes());
+    ssh_string(&mut signed, reserved);
+    ssh_string(&mut signed, hash_algorithm);
+    ssh_string(&mut signed, &hashed);
+    key.verify_strict(&signed, &sig)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const ARTIFACT: &[u8] = b"pretend this is a tarball";
+    const KEY_ID: [u8; 8] = *b"lifterid";
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn minisign_pubkey(sk: &SigningKey) -> String {
+        let mut raw = b"Ed".to_vec();
+        raw.extend_from_slice(&KEY_ID);
+        raw.extend_from_slice(sk.verifying_key().as_bytes());
+        format!("untrusted comment: test key\n{}\n", BASE64.encode(raw))
+    }
+
+    /// What `minisign -S` produces, built by hand.
+    fn minisign_sign(sk: &SigningKey, artifact: &[u8]) -> String {
+        let sig = sk.sign(&Blake2b512::digest(artifact));
+        let mut raw = b"ED".to_vec();
+        raw.extend_from_slice(&KEY_ID);
+        raw.extend_from_slice(&sig.to_bytes());
+        let trusted = "timestamp:1700000000\tfile:tool.tar.gz";
+        let mut global_msg = sig.to_bytes().to_vec();
+        global_msg.extend_from_slice(trusted.as_bytes());
+        let global = sk.sign(&global_msg);
+        format!(
+            "untrusted comment: signature from minisign secret key\n{}\ntrusted comment: {}\n{}\n",
+            BASE64.encode(raw),
+            trusted,
+            BASE64.encode(global.to_bytes())
+        )
+    }
+
+    fn ssh_key_blob(sk: &SigningKey) -> Vec<u8> {
+        let mut blob = Vec::new();
+        ssh_string(&mut blob, b"ssh-ed25519");
+        ssh_string(&mut blob, sk.verifying_key().as_bytes());
+        blob
+    }
+
+    fn ssh_pubkey(sk: &SigningKey) -> String {
+        format!("ssh-ed25519 {} me@host", BASE64.encode(ssh_key_blob(sk)))
+    }
+
+    /// What `ssh-keygen -Y sign -n <namespace>` produces, built by hand.
+    fn ssh_sign(sk: &SigningKey, namespace: &str, artifact: &[u8]) -> String {
+        let mut signed = SSHSIG_MAGIC.to_vec();
+        ssh_string(&mut signed, namespace.as_bytes());
+        ssh_string(&mut signed, b"");
+        ssh_string(&mut signed, b"sha512");
+        ssh_string(&mut signed, &Sha512::digest(artifact));
+        let mut sig_blob = Vec::new();
+        ssh_string(&mut sig_blob, b"ssh-ed25519");
+        ssh_string(&mut sig_blob, &sk.sign(&signed).to_bytes());
+
+        let mut blob = SSHSIG_MAGIC.to_vec();
+        blob.extend_from_slice(&1u32.to_be_bytes());
+        ssh_string(&mut blob, &ssh_key_blob(sk));
+        ssh_string(&mut blob, namespace.as_bytes());
+        ssh_string(&mut blob, b"");
+        ssh_string(&mut blob, b"sha512");
+        ssh_string(&mut blob, &sig_blob);
+        format!(
+            "-----BEGIN SSH SIGNATURE-----\n{}\n-----END SSH SIGNATURE-----\n",
+            BASE64.encode(blob)
+        )
+    }
+
+    #[test]
+    fn minisign_round_trip() {
+        let sk = signing_key(1);
+        let key = PublicKey::minisign(&minisign_pubkey(&sk)).unwrap();
+        let sig = minisign_sign(&sk, ARTIFACT);
+        assert!(key.verify(ARTIFACT, &sig).is_ok());
+        assert!(key.verify(&b"tampered"[..], &sig).is_err());
+    }
+
+    #[test]
+    fn minisign_rejects_other_key() {
+        let key = PublicKey::minisign(&minisign_pubkey(&signing_key(1))).unwrap();
+        let sig = minisign_sign(&signing_key(2), ARTIFACT);
+        assert!(key.verify(ARTIFACT, &sig).is_err());
+    }
+
+    #[test]
+    fn minisign_rejects_edited_trusted_comment() {
+        let sk = signing_key(1);
+        let key = PublicKey::minisign(&minisign_pubkey(&sk)).unwrap();
+        let sig = minisign_sign(&sk, ARTIFACT).replace("tool.tar.gz", "evil.tar.gz");
+        assert!(key.verify(ARTIFACT, &sig).is_err());
+    }
+
+    #[test]
+    fn ssh_round_trip() {
+        let sk = signing_key(3);
+        let key = PublicKey::ssh(&ssh_pubkey(&sk), "file").unwrap();
+        let sig = ssh_sign(&sk, "file", ARTIFACT);
+        assert!(key.verify(ARTIFACT, &sig).is_ok());
+        assert!(key.verify(&b"tampered"[..], &sig).is_err());
+    }
+
+    #[test]
+    fn ssh_rejects_wrong_namespace_and_key() {
+        let sk = signing_key(3);
+        let key = PublicKey::ssh(&ssh_pubkey(&sk), "file").unwrap();
+        assert!(key
+            .verify(ARTIFACT, &ssh_sign(&sk, "git", ARTIFACT))
+            .is_err());
+        let other = ssh_sign(&signing_key(4), "file", ARTIFACT);
+        assert!(key.verify(ARTIFACT, &other).is_err());
+    }
+
+    #[test]
+    fn malformed_keys_are_rejected() {
+        assert!(PublicKey::minisign("not base64!").is_err());
+        assert!(PublicKey::ssh("ssh-rsa AAAAB3NzaC1yc2E", "file").is_err());
+    }
+}
@@ -1,14 +1,14 @@
 use crate::archive::extract_targets_from_tar;
 use crate::Config;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 pub fn extract_target_from_tarfile(
-    compressed: &mut [u8],
+    compressed: impl Read,
     conf: &Config,
     output_dir: &Path,
 ) -> Vec<PathBuf> {
-    let cbuf = std::io::Cursor::new(compressed);
-    let gzip_archive = flate2::read::GzDecoder::new(cbuf);
+    let gzip_archive = flate2::read::GzDecoder::new(compressed);
     let mut archive = tar::Archive::new(gzip_archive);
     extract_targets_from_tar(&mut archive, conf, output_dir)
 }
@@ -1,14 +1,14 @@
 use crate::archive::extract_targets_from_tar;
 use crate::Config;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 pub fn extract_target_from_tarxz(
-    compressed: &mut [u8],
+    compressed: impl Read,
     conf: &Config,
     output_dir: &Path,
 ) -> Vec<PathBuf> {
-    let cbuf = std::io::Cursor::new(compressed);
-    let decompressor = xz2::read::XzDecoder::new(cbuf);
+    let decompressor = xz2::read::XzDecoder::new(compressed);
     let mut archive = tar::Archive::new(decompressor);
     extract_targets_from_tar(&mut archive, conf, output_dir)
 }
@@ -0,0 +1,189 @@
+//! Templates built into lifter.
+//!
+//! A section can name any of these with `template = ...` without the
+//! config defining it. A `[template:<name>]` section in the config
+//! replaces the built-in template of the same name entirely.
+//!
+//! `lifter templates` lists them, together with the config's own.
+
+use std::collections::HashMap;
+
+use crate::Templates;
+
+/// Config sections named `template:<name>` define a template.
+pub const TEMPLATE_PREFIX: &str = "template:";
+
+const BUILT_IN: &str = "\
+; GitHub's latest release, from the API.
+[template:github_api_latest]
+method = api_json
+page_url = https://api.github.com/repos/{project}/releases/latest
+version_tag = $.tag_name
+anchor_tag = $.assets.*.browser_download_url
+digest_tag = $.assets.*.digest
+
+; The GitHub release with the given `tag`, e.g. `tag = stable`.
+[template:github_api_tag]
+method = api_json
+page_url = https://api.github.com/repos/{project}/releases/tags/{tag}
+version_tag = $.tag_name
+anchor_tag = $.assets.*.browser_download_url
+digest_tag = $.assets.*.digest
+
+; GitLab.com's latest release. `project_id` is the numeric ID or the
+; URL-encoded path, e.g. `gitlab-org%2Fcli`.
+[template:gitlab_api_latest]
+method = api_json
+page_url = https://gitlab.com/api/v4/projects/{project_id}/releases/permalink/latest
+version_tag = $.tag_name
+anchor_tag = $.assets.links.*.direct_asset_url
+
+; The latest release on a Gitea or Forgejo server named by `host`.
+[template:gitea_api_latest]
+method = api_json
+page_url = https://{host}/api/v1/repos/{project}/releases/latest
+version_tag = $.tag_name
+anchor_tag = $.assets.*.browser_download_url
+
+; Codeberg's latest release.
+[template:codeberg_api_latest]
+method = api_json
+page_url = https://codeberg.org/api/v1/repos/{project}/releases/latest
+version_tag = $.tag_name
+anchor_tag = $.assets.*.browser_download_url
+
+; The file SourceForge offers as a project's download for each platform.
+; The JSON has no version field, so the section's `anchor_text` needs a
+; `(?P<version>...)` group.
+[template:sourceforge_best_release]
+method = api_json
+page_url = https://sourceforge.net/projects/{project}/best_release.json
+anchor_tag = $.platform_releases.*.url
+";
+
+/// Where a template comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    BuiltIn,
+    Config,
+    /// The config, replacing a built-in template of the same name.
+    ConfigOverride,
+}
+
+/// One template, with its fields in the order they were written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub name: String,
+    pub origin: Origin,
+    pub fields: Vec<(String, String)>,
+}
+
+impl Template {
+    /// The template as it would be written in a config.
+    pub fn to_ini(&self) -> String {
+        let mut text = format!("[{}{}]\n", TEMPLATE_PREFIX, self.name);
+        for (key, value) in &self.fields {
+            text.push_str(&format!("{key} = {value}\n"));
+        }
+        text
+    }
+}
+
+/// Whether lifter has a template called `name` built in.
+pub fn is_built_in(name: &str) -> bool {
+    built_in().iter().any(|t| t.name == name)
+}
+
+/// The built-in templates followed by those in `conf`, each in the order
+/// they're written, with every config template taking the place of the
+/// built-in one it overrides.
+pub fn collect(conf: &tini::Ini) -> Vec<Template> {
+    let mut templates = built_in();
+    for template in from_ini(conf, Origin::Config) {
+        match templates.iter_mut().find(|t| t.name == template.name) {
+            Some(built_in) => {
+                *built_in = Template {
+                    origin: Origin::ConfigOverride,
+                    ..template
+                }
+            }
+            None => templates.push(template),
+        }
+    }
+    templates
+}
+
+/// `templates` keyed by name, as `run_section` takes them.
+pub fn by_name(templates: &[Template]) -> Templates {
+    templates
+        .iter()
+        .map(|t| {
+            let fields: HashMap<_, _> = t.fields.iter().cloned().collect();
+            (t.name.clone(), fields)
+        })
+        .collect()
+}
+
+fn built_in() -> Vec<Template> {
+    let conf = tini::Ini::from_string(BUILT_IN).expect("built-in templates parse");
+    from_ini(&conf, Origin::BuiltIn)
+}
+
+fn from_ini(conf: &tini::Ini, origin: Origin) -> Vec<Template> {
+    conf.iter()
+        .filter_map(|(name, section)| {
+            Some(Template {
+                name: name.strip_prefix(TEMPLATE_PREFIX)?.to_string(),
+                origin,
+                fields: section
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn config_templates_replace_built_ins_of_the_same_name() -> Result<()> {
+        let conf = tini::Ini::from_string(
+            "[template:github_api_latest]\n\
+             method = api_json\n\
+             page_url = https://github.example.com/api/v3/repos/{project}/releases/latest\n\
+             [template:internal]\n\
+             page_url = https://downloads.example.com/{project}/\n\
+             [ripgrep]\n\
+             template = github_api_latest\n",
+        )?;
+        let templates = collect(&conf);
+        let github = templates
+            .iter()
+            .find(|t| t.name == "github_api_latest")
+            .unwrap();
+        assert_eq!(github.origin, Origin::ConfigOverride);
+        // Replaced outright, not merged field by field.
+        assert_eq!(github.fields.len(), 2);
+        assert_eq!(templates.last().unwrap().name, "internal");
+        assert_eq!(templates.last().unwrap().origin, Origin::Config);
+
+        let by_name = by_name(&templates);
+        assert!(by_name["gitlab_api_latest"]["page_url"].contains("{project_id}"));
+        assert_eq!(by_name.len(), built_in().len() + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn built_ins_round_trip_through_ini() -> Result<()> {
+        let templates = built_in();
+        assert!(is_built_in("github_api_latest") && !is_built_in("internal"));
+        let text: String = templates.iter().map(Template::to_ini).collect();
+        let conf = tini::Ini::from_string(text)?;
+        assert_eq!(from_ini(&conf, Origin::BuiltIn), templates);
+        Ok(())
+    }
+}
@@ -7,7 +7,8 @@
 
 use crate::{build_extraction_targets, Config};
 use std::collections::HashMap;
-use std::io::Write;
+use std::io::{Read, Write};
+use std::net::TcpListener;
 
 /// Build an in-memory uncompressed tar archive containing the given
 /// `(name, contents)` entries. Use with `tar::Archive::new(Cursor::new(bytes))`.
@@ -50,3 +51,36 @@ pub(crate) fn make_conf_from_ini(section: &str, pairs: &[(&str, &str)]) -> Confi
     conf.extraction_targets = build_extraction_targets(section, &tmp).unwrap();
     conf
 }
+
+/// A minimal HTTP server on 127.0.0.1 standing in for a release host.
+/// Every request is answered with the raw bytes `respond` returns for
+/// the request head (request line and headers), after which the
+/// connection is closed — unless it was a `CONNECT`, which lets it
+/// stand in for a proxy: the tunnelled request that follows is read
+/// and answered too. Returns the base URL, e.g. `http://127.0.0.1:PORT`.
+pub(crate) fn http_stand_in(respond: impl Fn(&str) -> Vec<u8> + Send + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            loop {
+                let mut head = Vec::new();
+                let mut byte = [0u8; 1];
+                while !head.ends_with(b"\r\n\r\n") {
+                    match stream.read(&mut byte) {
+                        Ok(1) => head.push(byte[0]),
+                        _ => break,
+                    }
+                }
+                let _ = stream.write_all(&respond(&String::from_utf8_lossy(&head)));
+                if !head.starts_with(b"CONNECT ") {
+                    break;
+                }
+            }
+        }
+    });
+    url
+}
@@ -0,0 +1,214 @@
+//! HTTP validators for each section's `page_url`, kept between runs.
+//!
+//! When a page (or API response) was fetched and its section ended up
+//! `updated` or `up_to_date`, the response's `ETag` and `Last-Modified`
+//! are saved in `.lifter-validators.json` next to the config. The next
+//! run sends them back as `If-None-Match` / `If-Modified-Since`, and a
+//! `304 Not Modified` means there's nothing new to look at. GitHub
+//! doesn't count 304s against the API rate limit, which is what makes
+//! frequent cron runs cheap.
+//!
+//! Entries are per section, not per URL: two sections often share a
+//! `page_url` (one release, several platforms), and one of them having
+//! seen the page says nothing about whether the other is installed.
+//! Each of the `platforms` of a section counts as a section here. An
+//! entry also remembers the installed version it was recorded with, and
+//! a hash of the fields that pick the asset and what's extracted from
+//! it. It's only used while both are unchanged, so editing `version`,
+//! `anchor_text` or the files to extract by hand forces a full fetch.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ureq::typestate::WithoutBody;
+use ureq::RequestBuilder;
+
+use crate::configfile::write_atomic;
+
+/// The validators of one response.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct PageValidators {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+}
+
+impl PageValidators {
+    /// The validators of `resp`, if it has any.
+    pub(crate) fn from_response(resp: &ureq::http::Response<ureq::Body>) -> Option<Self> {
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from)
+        };
+        let v = PageValidators {
+            etag: header("ETag"),
+            last_modified: header("Last-Modified"),
+        };
+        (v.etag.is_some() || v.last_modified.is_some()).then_some(v)
+    }
+
+    /// Make `request` conditional on the page having changed.
+    pub(crate) fn apply(
+        &self,
+        mut request: RequestBuilder<WithoutBody>,
+    ) -> RequestBuilder<WithoutBody> {
+        if let Some(etag) = &self.etag {
+            request = request.header("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header("If-Modified-Since", last_modified);
+        }
+        request
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    page_url: String,
+    version: String,
+    /// See `asset_selection`. Entries from before it was recorded
+    /// never match.
+    #[serde(default)]
+    selection: String,
+    #[serde(flatten)]
+    validators: PageValidators,
+}
+
+/// `.lifter-validators.json` and its entries, keyed by section.
+pub struct ValidatorStore {
+    path: PathBuf,
+    entries: Mutex<BTreeMap<String, Entry>>,
+}
+
+impl ValidatorStore {
+    /// Read the store at `path`. A missing or unreadable file is just
+    /// an empty cache: the worst outcome is a full fetch.
+    pub fn load(path: &Path) -> ValidatorStore {
+        let entries = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        ValidatorStore {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+        }
+    }
+
+    /// Validators to send for `section`, if they were recorded for the
+    /// same `page_url` and asset `selection` while `version` was
+    /// installed.
+    pub(crate) fn get(
+        &self,
+        section: &str,
+        page_url: &str,
+        version: &str,
+        selection: &str,
+    ) -> Option<PageValidators> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries
+            .get(section)
+            .filter(|e| e.page_url == page_url && e.version == version)
+            .filter(|e| e.selection == selection)
+            .map(|e| e.validators.clone())
+    }
+
+    pub(crate) fn record(
+        &self,
+        section: &str,
+        page_url: &str,
+        version: &str,
+        selection: &str,
+        validators: PageValidators,
+    ) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.insert(
+            section.to_string(),
+            Entry {
+                page_url: page_url.to_string(),
+                version: version.to_string(),
+                selection: selection.to_string(),
+                validators,
+            },
+        );
+        let text = serde_json::to_string_pretty(&*entries)?;
+        write_atomic(&self.path, text.as_bytes())
+            .map_err(|e| anyhow!("Failed to save HTTP validators: {}", e))
+    }
+}
+
+/// Hash of the fields that pick a section's asset and what's taken out
+/// of it. Validators recorded before one of them was edited would
+/// answer `304` for a page that now means a different download.
+pub(crate) fn asset_selection<'a>(fields: impl IntoIterator<Item = &'a str>) -> String {
+    let mut hasher = Sha256::new();
+    for field in fields {
+        hasher.update(field.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn etag(value: &str) -> PageValidators {
+        PageValidators {
+            etag: Some(value.to_string()),
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn entries_round_trip_and_match_url_and_version() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(".lifter-validators.json");
+        let url = "https://api.github.com/repos/BurntSushi/ripgrep/releases/latest";
+        let selection = asset_selection(["a", "ripgrep-.*-x86_64-unknown-linux-musl.tar.gz"]);
+        ValidatorStore::load(&path).record(
+            "ripgrep",
+            url,
+            "14.1.1",
+            &selection,
+            etag("W/\"abc\""),
+        )?;
+
+        let store = ValidatorStore::load(&path);
+        assert_eq!(
+            store.get("ripgrep", url, "14.1.1", &selection),
+            Some(etag("W/\"abc\""))
+        );
+        assert_eq!(store.get("ripgrep", url, "14.1.0", &selection), None);
+        assert_eq!(
+            store.get("ripgrep", "https://example.com/", "14.1.1", &selection),
+            None
+        );
+        assert_eq!(
+            store.get("ripgrep Windows", url, "14.1.1", &selection),
+            None
+        );
+
+        let edited = asset_selection(["a", "ripgrep-.*-aarch64-unknown-linux-musl.tar.gz"]);
+        assert_eq!(store.get("ripgrep", url, "14.1.1", &edited), None);
+        Ok(())
+    }
+
+    #[test]
+    fn corrupt_file_is_an_empty_store() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(".lifter-validators.json");
+        std::fs::write(&path, "{ not json")?;
+        assert_eq!(
+            ValidatorStore::load(&path).get("ripgrep", "u", "1", ""),
+            None
+        );
+        Ok(())
+    }
+}
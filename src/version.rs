@@ -0,0 +1,273 @@
+//! Version ordering for the up-to-date check.
+//!
+//! Remote versions come from wherever the section scrapes them — a
+//! GitHub `tag_name`, the text of a DOM element — so they arrive in
+//! every shape: `v0.10.0`, `14.1.0`, `1.2.3.4`, `2.0.0-rc.1`,
+//! `2024.01.15`, `nightly`. Comparing those as plain strings gets
+//! `v0.10.0 < v0.9.0`, which made lifter skip real updates.
+//!
+//! Each section picks a [`VersionScheme`] with the `version_scheme`
+//! key (default `semver`):
+//!
+//!   * `semver` — dot-separated numeric components (any count), an
+//!     optional `-pre.release` suffix that sorts *before* the release,
+//!     and optional `+build` metadata that is ignored.
+//!   * `calver` — date-style versions; `-` and `_` separate numeric
+//!     components rather than introducing a pre-release, and a compact
+//!     `YYYYMMDD` component is split into year, month and day.
+//!   * `opaque` — no ordering at all; versions are either identical or
+//!     different. Useful for tags like `nightly` or `First release`.
+//!
+//! Any leading non-digit prefix (`v`, `release-`, `ripgrep-`) is
+//! ignored. A version that can't be parsed under its scheme (no digits
+//! at all) falls back to opaque comparison.
+
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+
+/// How the versions of one section are ordered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VersionScheme {
+    #[default]
+    Semver,
+    Calver,
+    Opaque,
+}
+
+impl FromStr for VersionScheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "semver" => Ok(VersionScheme::Semver),
+            "calver" => Ok(VersionScheme::Calver),
+            "opaque" => Ok(VersionScheme::Opaque),
+            other => Err(anyhow!(
+                "unknown version_scheme {:?}; expected one of semver, calver, opaque",
+                other
+            )),
+        }
+    }
+}
+
+impl VersionScheme {
+    /// Order `found` relative to `existing`.
+    ///
+    /// `Some(Equal)` means "same version". `None` means the two can't
+    /// be ordered (opaque scheme, or unparseable input) *and* are not
+    /// identical — callers should treat that as "changed".
+    pub(crate) fn compare(&self, found: &str, existing: &str) -> Option<Ordering> {
+        let (found, existing) = (found.trim(), existing.trim());
+        if found == existing {
+            return Some(Ordering::Equal);
+        }
+        if *self == VersionScheme::Opaque {
+            return None;
+        }
+        let a = ParsedVersion::parse(found, *self)?;
+        let b = ParsedVersion::parse(existing, *self)?;
+        Some(a.cmp(&b))
+    }
+}
+
+/// One run of a pre-release suffix. Numeric runs sort before
+/// alphanumeric ones, as in semver §11.4.3.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum PreIdent {
+    Numeric(u64),
+    Alpha(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ParsedVersion {
+    release: Vec<u64>,
+    /// Empty when this is a full release.
+    pre: Vec<PreIdent>,
+}
+
+impl ParsedVersion {
+    fn parse(raw: &str, scheme: VersionScheme) -> Option<ParsedVersion> {
+        let start = raw.find(|c: char| c.is_ascii_digit())?;
+        let s = &raw[start..];
+        // Build metadata never participates in ordering.
+        let s = s.split('+').next().unwrap_or(s);
+
+        let is_separator = |c: char| match scheme {
+            VersionScheme::Calver => c == '.' || c == '-' || c == '_',
+            _ => c == '.',
+        };
+
+        let mut release = Vec::new();
+        let mut rest = s;
+        loop {
+            let digits_end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            if digits_end == 0 {
+                break;
+            }
+            let digits = &rest[..digits_end];
+            if scheme == VersionScheme::Calver && release.is_empty() && digits.len() == 8 {
+                // Compact `YYYYMMDD`.
+                release.push(digits[..4].parse().ok()?);
+                release.push(digits[4..6].parse().ok()?);
+                release.push(digits[6..].parse().ok()?);
+            } else {
+                release.push(digits.parse().ok()?);
+            }
+            rest = &rest[digits_end..];
+            match rest.chars().next() {
+                Some(c)
+                    if is_separator(c) && rest[1..].starts_with(|c: char| c.is_ascii_digit()) =>
+                {
+                    rest = &rest[1..];
+                }
+                _ => break,
+            }
+        }
+
+        Some(ParsedVersion {
+            release,
+            pre: parse_pre(rest),
+        })
+    }
+}
+
+/// Split whatever follows the numeric release into comparable runs,
+/// e.g. `-rc.10` → `[Alpha("rc"), Numeric(10)]` and `beta2` →
+/// `[Alpha("beta"), Numeric(2)]`, so `rc10` sorts after `rc9`.
+fn parse_pre(rest: &str) -> Vec<PreIdent> {
+    let mut out = Vec::new();
+    let mut chars = rest.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_ascii_digit() {
+            let mut n = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                n.push(d);
+                chars.next();
+            }
+            out.push(PreIdent::Numeric(n.parse().unwrap_or(u64::MAX)));
+        } else if c.is_alphanumeric() {
+            let mut s = String::new();
+            while let Some(&a) = chars
+                .peek()
+                .filter(|a| a.is_alphanumeric() && !a.is_ascii_digit())
+            {
+                s.push(a.to_ascii_lowercase());
+                chars.next();
+            }
+            out.push(PreIdent::Alpha(s));
+        } else {
+            chars.next();
+        }
+    }
+    out
+}
+
+impl Ord for ParsedVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let len = self.release.len().max(other.release.len());
+        let component = |v: &[u64], i: usize| v.get(i).copied().unwrap_or(0);
+        for i in 0..len {
+            match component(&self.release, i).cmp(&component(&other.release, i)) {
+                Ordering::Equal => continue,
+                ord => return ord,
+            }
+        }
+        // A pre-release sorts before the release it precedes.
+        match (self.pre.is_empty(), other.pre.is_empty()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => self.pre.cmp(&other.pre),
+        }
+    }
+}
+
+impl PartialOrd for ParsedVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Ordering::*;
+
+    fn semver(a: &str, b: &str) -> Option<Ordering> {
+        VersionScheme::Semver.compare(a, b)
+    }
+
+    #[test]
+    fn numeric_components_compare_as_integers() {
+        assert_eq!(semver("v0.10.0", "v0.9.0"), Some(Greater));
+        assert_eq!(semver("14.1.0", "9.0.0"), Some(Greater));
+        assert_eq!(semver("9.0.0", "14.1.0"), Some(Less));
+    }
+
+    #[test]
+    fn v_prefix_is_ignored() {
+        assert_eq!(semver("v1.2.3", "1.2.3"), Some(Equal));
+        assert_eq!(semver("ripgrep-14.1.1", "14.1.0"), Some(Greater));
+    }
+
+    #[test]
+    fn four_components_and_missing_components() {
+        assert_eq!(semver("1.2.3.4", "1.2.3.10"), Some(Less));
+        assert_eq!(semver("1.2", "1.2.0"), Some(Equal));
+        assert_eq!(semver("1.2.0.1", "1.2"), Some(Greater));
+    }
+
+    #[test]
+    fn pre_release_sorts_before_release() {
+        assert_eq!(semver("2.0.0-rc.1", "2.0.0"), Some(Less));
+        assert_eq!(semver("2.0.0", "2.0.0-rc.1"), Some(Greater));
+        assert_eq!(semver("2.0.0-rc.10", "2.0.0-rc.9"), Some(Greater));
+        assert_eq!(semver("2.0.0-beta2", "2.0.0-alpha7"), Some(Greater));
+        assert_eq!(semver("2.0.0-rc.1", "1.9.9"), Some(Greater));
+    }
+
+    #[test]
+    fn build_metadata_is_ignored() {
+        assert_eq!(semver("1.0.0+abc", "1.0.0+def"), Some(Equal));
+    }
+
+    #[test]
+    fn calver_dates() {
+        let c = VersionScheme::Calver;
+        assert_eq!(c.compare("2024.01.15", "2023.12.31"), Some(Greater));
+        assert_eq!(c.compare("2024-02-01", "2024-01-31"), Some(Greater));
+        assert_eq!(c.compare("20240201", "2024.1.31"), Some(Greater));
+        assert_eq!(c.compare("v2024.10", "v2024.9"), Some(Greater));
+        assert_eq!(
+            c.compare("nightly-2024-01-02", "nightly-2024-01-02"),
+            Some(Equal)
+        );
+    }
+
+    #[test]
+    fn opaque_is_only_equal_or_different() {
+        let o = VersionScheme::Opaque;
+        assert_eq!(o.compare("nightly", "nightly"), Some(Equal));
+        assert_eq!(o.compare("v2.0.0", "v1.0.0"), None);
+    }
+
+    #[test]
+    fn unparseable_falls_back_to_opaque() {
+        assert_eq!(semver("First release", "First release"), Some(Equal));
+        assert_eq!(semver("Alpha", "Beta"), None);
+        assert_eq!(semver("nightly", "1.0.0"), None);
+    }
+
+    #[test]
+    fn scheme_parses_from_config_value() {
+        assert_eq!(
+            "CalVer".parse::<VersionScheme>().unwrap(),
+            VersionScheme::Calver
+        );
+        assert!("lexical".parse::<VersionScheme>().is_err());
+    }
+}
@@ -3,7 +3,7 @@ use crate::archive::{
 };
 use crate::Config;
 use log::debug;
-use std::io::{Read, Write};
+use std::io::{Read, Seek};
 use std::path::{Path, PathBuf};
 
 /// Walk the zip once, fulfilling each `ExtractionTarget` in `conf` on
@@ -12,12 +12,11 @@ use std::path::{Path, PathBuf};
 /// paths actually written. Targets that never match are warned about
 /// but do not error — keeps a typo'd plural entry from killing the run.
 pub fn extract_target_from_zipfile(
-    compressed: &mut [u8],
+    compressed: impl Read + Seek,
     conf: &Config,
     output_dir: &Path,
 ) -> anyhow::Result<Vec<PathBuf>> {
-    let mut cbuf = std::io::Cursor::new(compressed);
-    let mut archive = zip::ZipArchive::new(&mut cbuf)?;
+    let mut archive = zip::ZipArchive::new(compressed)?;
 
     let mut state = init_target_states(conf);
     let mut written: Vec<PathBuf> = Vec::new();
@@ -42,9 +41,7 @@ pub fn extract_target_from_zipfile(
         let out_path = output_dir.join(out_name);
         debug!("zip, Got a match: {} -> {}", &fname, out_path.display());
         let mut entry = archive.by_name(&fname)?;
-        let mut payload = Vec::new();
-        entry.read_to_end(&mut payload)?;
-        std::fs::File::create(&out_path)?.write_all(&payload)?;
+        std::io::copy(&mut entry, &mut std::fs::File::create(&out_path)?)?;
         slot.fulfilled = true;
         written.push(out_path);
     }